@@ -11,9 +11,29 @@ mod protocol;
 /// This macro takes two parameters separated by a comma `protocol!(protocol_path, file_path)` and generates the associated rust structures:
 /// - `protocol_path`: the absolute path to the `wayne-protocol` crate.
 /// - `file_path`: the path to the protocol xml file relative to the crate root.
+///
+/// Any number of comma separated paths to already generated protocol modules may follow,
+/// which are imported so that references to their interfaces resolve.
+/// An `only = ["interface", ...]` option may also follow to generate a subset of the interfaces.
 #[proc_macro]
 pub fn protocol(input: TokenStream) -> TokenStream {
     parse_macro_input!(input as protocol::Generator)
         .into_token_stream()
         .into()
 }
+
+/// ⚠️ __This is the raw macro implementation for `wayne_protocol::generate_str` and is not usually intended to be used on its own.__ ⚠️
+///
+/// It is preferred to use `wayne_protocol::generate_str!(xml)` instead as the crate path does not need to be specified.
+///
+/// This macro takes two parameters separated by a comma `protocol_str!(protocol_path, xml)` and generates the associated rust structures:
+/// - `protocol_path`: the absolute path to the `wayne-protocol` crate.
+/// - `xml`: a string literal containing the protocol xml.
+///
+/// The same options as `protocol!` may follow the xml.
+#[proc_macro]
+pub fn protocol_str(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as protocol::StrGenerator)
+        .into_token_stream()
+        .into()
+}
@@ -1,11 +1,12 @@
 use std::{env, fs::File, io::BufReader, path::PathBuf};
 
 use convert_case::{Case, Casing};
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{ToTokens, quote};
 use syn::{
-    LitStr, Path, Token,
+    Ident, LitStr, Path, Token, bracketed,
     parse::{Parse, ParseStream},
+    punctuated::Punctuated,
 };
 
 use super::xml::{Arg, ArgType, Enum, Event, Interface, Protocol, Request};
@@ -13,6 +14,141 @@ use super::xml::{Arg, ArgType, Enum, Event, Interface, Protocol, Request};
 pub struct Generator {
     protocol_path: Path,
     protocol: Protocol,
+    extern_paths: Box<[Path]>,
+}
+
+/// The optional trailing arguments of the protocol macros
+///
+/// These are a list of already generated protocol modules, whose interfaces are imported so that
+/// cross-protocol references resolve, and an `only = ["name", ...]` filter of interfaces to generate.
+struct Options {
+    extern_paths: Box<[Path]>,
+    only: Option<Vec<LitStr>>,
+}
+
+impl Parse for Options {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut extern_paths = Vec::new();
+        let mut only = None;
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            if !(input.peek(Ident) && input.peek2(Token![=])) {
+                extern_paths.push(input.parse::<Path>()?);
+                continue;
+            }
+
+            let option = input.parse::<Ident>()?;
+            let _ = input.parse::<Token![=]>()?;
+            if option != "only" {
+                return Err(syn::Error::new(
+                    option.span(),
+                    "unknown option, expected `only`",
+                ));
+            }
+
+            let content;
+            bracketed!(content in input);
+            let names = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+            only = Some(names.into_iter().collect());
+        }
+
+        Ok(Self {
+            extern_paths: extern_paths.into_boxed_slice(),
+            only,
+        })
+    }
+}
+
+impl Options {
+    /// Builds a generator for `protocol`, removing any interfaces excluded by `only`
+    ///
+    /// Fails if `only` names an interface that does not exist, or keeps an interface
+    /// that references one from this protocol that was removed.
+    /// Also fails if the protocol has no interfaces, or an interface has more requests or events
+    /// than fit in a `u16` opcode, in which case errors are reported at the `source` span.
+    fn generator(
+        self,
+        protocol_path: Path,
+        mut protocol: Protocol,
+        source: Span,
+    ) -> syn::Result<Generator> {
+        // opcodes are 16 bits on the wire, so every message must be addressable by one.
+        // the count itself must also fit in a u16, as the generated opcode ranges use it.
+        const MAX_MESSAGES: usize = u16::MAX as usize;
+
+        // an empty protocol would silently generate no bindings
+        if protocol.interfaces.is_empty() {
+            let message = format!(
+                "protocol '{}' does not contain any interfaces",
+                protocol.name
+            );
+            return Err(syn::Error::new(source, message));
+        }
+
+        for interface in &protocol.interfaces {
+            for (kind, count) in [
+                ("requests", interface.requests.len()),
+                ("events", interface.events.len()),
+            ] {
+                if count > MAX_MESSAGES {
+                    let message = format!(
+                        "interface '{}' has {count} {kind}, but at most {MAX_MESSAGES} are supported",
+                        interface.name
+                    );
+                    return Err(syn::Error::new(source, message));
+                }
+            }
+        }
+
+        if let Some(only) = &self.only {
+            for name in only {
+                if !protocol.interfaces.iter().any(|i| i.name == name.value()) {
+                    let message = format!(
+                        "protocol does not contain an interface named '{}'",
+                        name.value()
+                    );
+                    return Err(syn::Error::new(name.span(), message));
+                }
+            }
+
+            let (kept, removed): (Vec<_>, Vec<_>) = protocol
+                .interfaces
+                .into_iter()
+                .partition(|i| only.iter().any(|name| i.name == name.value()));
+
+            for interface in &kept {
+                let Some(missing) = interface
+                    .references()
+                    .find(|name| removed.iter().any(|i| i.name == *name))
+                else {
+                    continue;
+                };
+
+                let span = only
+                    .iter()
+                    .find(|name| interface.name == name.value())
+                    .unwrap()
+                    .span();
+                let message = format!(
+                    "interface '{}' references '{missing}', which must also be included in `only`",
+                    interface.name
+                );
+                return Err(syn::Error::new(span, message));
+            }
+
+            protocol.interfaces = kept;
+        }
+
+        for interface in &mut protocol.interfaces {
+            interface.mark_global_names();
+        }
+        protocol.mark_bitfields();
+
+        Ok(Generator {
+            protocol_path,
+            protocol,
+            extern_paths: self.extern_paths,
+        })
+    }
 }
 
 impl Parse for Generator {
@@ -20,25 +156,99 @@ impl Parse for Generator {
         let protocol_path = input.parse::<Path>()?;
         let _ = input.parse::<Token![,]>()?;
         let file_path = input.parse::<LitStr>()?;
+        let options = input.parse::<Options>()?;
         let root_path: PathBuf = env::var("CARGO_MANIFEST_DIR").unwrap().into();
         match File::open(root_path.join(file_path.value())) {
             Err(err) => Err(syn::Error::new(file_path.span(), err)),
             Ok(file) => match quick_xml::de::from_reader::<_, Protocol>(BufReader::new(file)) {
                 Err(err) => Err(syn::Error::new(file_path.span(), err)),
-                Ok(protocol) => Ok(Self {
-                    protocol_path,
-                    protocol,
-                }),
+                Ok(protocol) => options.generator(protocol_path, protocol, file_path.span()),
             },
         }
     }
 }
 
+/// A [`Generator`] that reads the protocol xml from an inline string literal
+pub struct StrGenerator(Generator);
+
+impl Parse for StrGenerator {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let protocol_path = input.parse::<Path>()?;
+        let _ = input.parse::<Token![,]>()?;
+        let xml = input.parse::<LitStr>()?;
+        let options = input.parse::<Options>()?;
+        match quick_xml::de::from_str::<Protocol>(&xml.value()) {
+            Err(err) => Err(syn::Error::new(xml.span(), err)),
+            Ok(protocol) => Ok(Self(options.generator(
+                protocol_path,
+                protocol,
+                xml.span(),
+            )?)),
+        }
+    }
+}
+
+impl ToTokens for StrGenerator {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.0.to_tokens(tokens);
+    }
+}
+
+/// The number of interfaces a single protocol can generate before a warning is emitted
+const LARGE_PROTOCOL_INTERFACES: usize = 64;
+
 impl ToTokens for Generator {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let protocol_path = &self.protocol_path;
-        let ident = utils::ident(&self.protocol.name);
+        let protocol_name = &self.protocol.name;
+        let extern_paths = &self.extern_paths;
+        let ident = utils::ident(protocol_name);
         let interface_types = self.protocol.interfaces.iter().map(Type);
+        let interface_count = self.protocol.interfaces.len();
+
+        // there is no stable way to emit warnings from a proc macro,
+        // so a deprecated item is used to get the compiler to emit one
+        let large_warning = (interface_count > LARGE_PROTOCOL_INTERFACES).then(|| {
+            let note = format!(
+                "protocol '{protocol_name}' generates {interface_count} interfaces, \
+                which may slow down compilation. consider splitting it into multiple protocols"
+            );
+
+            quote! {
+                const _: () = {
+                    #[deprecated(note = #note)]
+                    const LARGE_PROTOCOL: () = ();
+                    LARGE_PROTOCOL
+                };
+            }
+        });
+
+        let interface_names = self
+            .protocol
+            .interfaces
+            .iter()
+            .map(|interface| &interface.name)
+            .collect::<Box<[_]>>();
+        let interface_mods = self
+            .protocol
+            .interfaces
+            .iter()
+            .map(|interface| utils::ident(&interface.name))
+            .collect::<Box<[_]>>();
+        let interface_variants = self
+            .protocol
+            .interfaces
+            .iter()
+            .map(|interface| utils::ident(interface.name.to_case(Case::Pascal)))
+            .collect::<Box<[_]>>();
+        let interface_requests = interface_variants
+            .iter()
+            .map(|variant| utils::ident(format!("{variant}Request")));
+        let interface_parsers = interface_variants
+            .iter()
+            .map(|variant| utils::ident(format!("{variant}Parser")))
+            .collect::<Box<[_]>>();
+
         tokens.extend(quote! {
             pub mod #ident {
                 #[allow(unused_imports)]
@@ -47,18 +257,156 @@ impl ToTokens for Generator {
                 // re-export useful protocol items
                 #[allow(unused_imports)]
                 use #protocol_path::{
-                    Parser, Buffer,
-                    parser::Builder,
+                    Parser, ParseError, Buffer, Interface,
+                    // the protocol level parser enum is also named RequestParser
+                    interface::RequestParser as RequestParserTrait,
+                    parser::{Builder, BoxedParser, parse_all, check_fd_count},
+                    object::{ObjectMap, ObjectError, Destructor},
                     types::{
-                        RawEnum, RawString,
-                        id::{NewId, CustomNewId, ObjectId},
+                        Bitfield, Flags, RawEnum, RawString,
+                        id::{NewId, CustomNewId, ObjectId, GlobalName},
                     },
                     parse::{
                         int, uint, float, string, custom, array, fd,
                     },
                 };
 
+                // import interfaces from other protocols this one references
+                #(
+                    #[allow(unused_imports)]
+                    use #extern_paths::*;
+                )*
+
+                /// The name of the protocol these items were generated from
+                pub const PROTOCOL_NAME: &str = #protocol_name;
+
+                /// The number of interfaces in this protocol
+                pub const INTERFACE_COUNT: usize = #interface_count;
+
+                #large_warning
+
                 #(#interface_types)*
+
+                /// A request for any interface in this protocol
+                #[derive(Debug)]
+                pub enum Request {
+                    #(#interface_variants(#interface_mods::#interface_requests),)*
+                }
+
+                impl Request {
+                    /// Returns the number of fds this request took from the fd buffer when parsed
+                    pub fn fd_count(&self) -> usize {
+                        match self {
+                            #(Self::#interface_variants(request) => request.fd_count(),)*
+                        }
+                    }
+
+                    /// Ensures every typed object argument references a live object of the correct interface
+                    pub fn validate_objects<D>(&self, map: &ObjectMap<D>) -> Result<(), ObjectError> {
+                        match self {
+                            #(Self::#interface_variants(request) => request.validate_objects(map),)*
+                        }
+                    }
+
+                    /// Registers every object created by a `new_id` argument of this request in `map`
+                    pub fn register_new_ids<D: Default>(&self, map: &mut ObjectMap<D>, version: u32) -> Result<(), ObjectError> {
+                        match self {
+                            #(Self::#interface_variants(request) => request.register_new_ids(map, version),)*
+                        }
+                    }
+                }
+
+                impl Destructor for Request {
+                    fn is_destructor(&self) -> bool {
+                        match self {
+                            #(Self::#interface_variants(request) => request.is_destructor(),)*
+                        }
+                    }
+                }
+
+                /// A request parser for any interface in this protocol
+                pub enum RequestParser {
+                    #(#interface_variants(#interface_mods::#interface_parsers),)*
+                }
+
+                impl RequestParser {
+                    pub fn new(interface: &str, opcode: u16) -> Option<Self> {
+                        match interface {
+                            #(#interface_names => Some(Self::#interface_variants(
+                                #interface_mods::#interface_parsers::new(opcode)?
+                            )),)*
+                            _ => None,
+                        }
+                    }
+                }
+
+                impl Parser for RequestParser {
+                    type Output = Request;
+
+                    fn parse(&mut self, bytes: impl Buffer<u8>, fds: impl Buffer<OwnedFd>) -> Result<Self::Output, ParseError> {
+                        match self {
+                            #(Self::#interface_variants(parser) => Ok(
+                                Request::#interface_variants(parser.parse(bytes, fds)?)
+                            ),)*
+                        }
+                    }
+                }
+
+                /// Returns the opcodes of every destructor request on the interface named `interface`
+                ///
+                /// Returns `None` if the interface is not part of this protocol
+                pub fn destructor_requests(interface: &str) -> Option<&'static [u16]> {
+                    match interface {
+                        #(#interface_names => Some(#interface_mods::DESTRUCTOR_REQUESTS),)*
+                        _ => None,
+                    }
+                }
+
+                /// Returns the opcodes of every destructor event on the interface named `interface`
+                ///
+                /// Returns `None` if the interface is not part of this protocol
+                pub fn destructor_events(interface: &str) -> Option<&'static [u16]> {
+                    match interface {
+                        #(#interface_names => Some(#interface_mods::DESTRUCTOR_EVENTS),)*
+                        _ => None,
+                    }
+                }
+
+                /// Returns the name of the request with `opcode` on the interface named `interface`
+                ///
+                /// Returns `None` if the interface is not part of this protocol or the opcode is invalid
+                pub fn request_name(interface: &str, opcode: u16) -> Option<&'static str> {
+                    match interface {
+                        #(#interface_names => #interface_mods::request_name(opcode),)*
+                        _ => None,
+                    }
+                }
+
+                /// Returns the name of the event with `opcode` on the interface named `interface`
+                ///
+                /// Returns `None` if the interface is not part of this protocol or the opcode is invalid
+                pub fn event_name(interface: &str, opcode: u16) -> Option<&'static str> {
+                    match interface {
+                        #(#interface_names => #interface_mods::event_name(opcode),)*
+                        _ => None,
+                    }
+                }
+
+                /// Returns a parser for the request with `opcode` on the interface named `interface`
+                ///
+                /// Returns `None` if the interface is not part of this protocol or the opcode is invalid
+                pub fn parser_for(interface: &str, opcode: u16) -> Option<RequestParser> {
+                    RequestParser::new(interface, opcode)
+                }
+
+                /// Returns a boxed parser for the request with `opcode` on the interface named `interface`
+                ///
+                /// This is useful for dispatchers that store parsers from many protocols together.
+                ///
+                /// Returns `None` if the interface is not part of this protocol or the opcode is invalid
+                pub fn request_parser(interface: &str, opcode: u16) -> Option<BoxedParser<'static, Request>> {
+                    Some(Box::new(RequestParser::new(interface, opcode)?))
+                }
             }
         });
     }
@@ -79,7 +427,7 @@ impl ToTokens for Type<&Interface> {
         let pascal_name = self.0.name.to_case(Case::Pascal);
         let main_enum = utils::ident(&pascal_name);
 
-        let request_enum = utils::ident(&format!("{pascal_name}Request"));
+        let request_enum = utils::ident(format!("{pascal_name}Request"));
         let request_variants = self.0.requests.iter().map(|request| {
             let name = request.name.to_case(Case::Pascal);
             let variant = utils::ident(&name);
@@ -95,8 +443,9 @@ impl ToTokens for Type<&Interface> {
             quote! { #variant(#item) }
         });
 
+        // the number of messages is validated to fit in a u16 opcode when parsing the protocol
         let opcodes = 0..(self.0.requests.len() as u16);
-        let parser_enum = utils::ident(&format!("{pascal_name}Parser"));
+        let parser_enum = utils::ident(format!("{pascal_name}Parser"));
         let parser_variants = self.0.requests.iter().map(|request| {
             let name = request.name.to_case(Case::Pascal);
             let variant = utils::ident(&name);
@@ -112,13 +461,45 @@ impl ToTokens for Type<&Interface> {
         let parser_parse = self.0.requests.iter().map(|request| {
             let variant = utils::ident(request.name.to_case(Case::Pascal));
             quote! {
-                Self::#variant(parser) => Some(
+                Self::#variant(parser) => Ok(
                     #request_enum::#variant(parser.parse(bytes, fds)?)
                 )
             }
         });
 
         let request_parsers = self.0.requests.iter().map(Parser);
+        let interface_name = &self.0.name;
+        let request_variant_names = self
+            .0
+            .requests
+            .iter()
+            .map(|request| utils::ident(request.name.to_case(Case::Pascal)))
+            .collect::<Box<[_]>>();
+        let request_destructors = self.0.requests.iter().map(|request| request.destructor);
+        let request_structs =
+            self.0.requests.iter().map(|request| {
+                utils::ident(format!("{}Request", request.name.to_case(Case::Pascal)))
+            });
+        let destructor_requests = (0u16..)
+            .zip(&self.0.requests)
+            .filter_map(|(opcode, request)| request.destructor.then_some(opcode));
+
+        let event_variant_names = self
+            .0
+            .events
+            .iter()
+            .map(|event| utils::ident(event.name.to_case(Case::Pascal)));
+        let event_destructors = self.0.events.iter().map(|event| event.destructor);
+        let destructor_events = (0u16..)
+            .zip(&self.0.events)
+            .filter_map(|(opcode, event)| event.destructor.then_some(opcode));
+
+        let request_count = self.0.requests.len() as u16;
+        let event_count = self.0.events.len() as u16;
+        let request_opcodes = 0..(self.0.requests.len() as u16);
+        let request_names = self.0.requests.iter().map(|request| &request.name);
+        let event_opcodes = 0..(self.0.events.len() as u16);
+        let event_names = self.0.events.iter().map(|event| &event.name);
 
         tokens.extend(quote! {
             pub use #mod_ident::#main_enum;
@@ -127,7 +508,14 @@ impl ToTokens for Type<&Interface> {
             pub mod #mod_ident {
                 use super::*;
 
-                const VERSION: u32 = #version;
+                /// The version of this interface these items were generated from
+                pub const VERSION: u32 = #version;
+
+                /// The number of requests on this interface
+                pub const REQUEST_COUNT: u16 = #request_count;
+
+                /// The number of events on this interface
+                pub const EVENT_COUNT: u16 = #event_count;
 
                 #[derive(Debug)]
                 pub enum #main_enum {
@@ -135,6 +523,28 @@ impl ToTokens for Type<&Interface> {
                     Event(#event_enum),
                 }
 
+                impl #main_enum {
+                    /// The name of this interface as it appears on the wire
+                    pub const INTERFACE: &'static str = #interface_name;
+
+                    /// The version of this interface these items were generated from
+                    pub const VERSION: u32 = VERSION;
+                }
+
+                impl Interface for #main_enum {
+                    const NAME: &'static str = Self::INTERFACE;
+                    const VERSION: u32 = VERSION;
+                }
+
+                impl RequestParserTrait for #main_enum {
+                    type Request = #request_enum;
+                    const REQUEST_COUNT: u16 = REQUEST_COUNT;
+
+                    fn parse_request(opcode: u16, body: &[u8], fds: &mut impl Buffer<OwnedFd>) -> Result<#request_enum, ParseError> {
+                        #request_enum::parse(opcode, body, fds)
+                    }
+                }
+
                 #[derive(Debug)]
                 pub enum #request_enum {
                     #(#request_variants,)*
@@ -144,6 +554,51 @@ impl ToTokens for Type<&Interface> {
                     pub fn parser(opcode: u16) -> Option<#parser_enum> {
                         #parser_enum::new(opcode)
                     }
+
+                    /// Parses a complete request with `opcode` from a message `body`
+                    ///
+                    /// Any fds the request carries are taken from `fds`, which should hold only the fds
+                    /// that were sent with this message. If `fds` knows how many it holds, that number is
+                    /// checked against the request's `FD_COUNT` with [`check_fd_count`], which catches
+                    /// a peer attaching fds that the request does not take.
+                    pub fn parse(opcode: u16, body: &[u8], fds: &mut impl Buffer<OwnedFd>) -> Result<Self, ParseError> {
+                        let mut parser = Self::parser(opcode).ok_or(ParseError::UnknownOpcode { interface: #interface_name, opcode })?;
+                        let available = fds.available();
+                        let request = parse_all(&mut parser, body, &mut *fds)?;
+                        if let Some(available) = available {
+                            check_fd_count(#interface_name, opcode, request.fd_count(), available);
+                        }
+                        Ok(request)
+                    }
+
+                    /// Returns the number of fds this request took from the fd buffer when parsed
+                    pub fn fd_count(&self) -> usize {
+                        match *self {
+                            #(Self::#request_variant_names(_) => #request_structs::FD_COUNT,)*
+                        }
+                    }
+
+                    /// Ensures every typed object argument references a live object of the correct interface
+                    pub fn validate_objects<D>(&self, map: &ObjectMap<D>) -> Result<(), ObjectError> {
+                        match *self {
+                            #(Self::#request_variant_names(ref request) => request.validate_objects(map),)*
+                        }
+                    }
+
+                    /// Registers every object created by a `new_id` argument of this request in `map`
+                    pub fn register_new_ids<D: Default>(&self, map: &mut ObjectMap<D>, version: u32) -> Result<(), ObjectError> {
+                        match *self {
+                            #(Self::#request_variant_names(ref request) => request.register_new_ids(map, version),)*
+                        }
+                    }
+                }
+
+                impl Destructor for #request_enum {
+                    fn is_destructor(&self) -> bool {
+                        match *self {
+                            #(Self::#request_variant_names(_) => #request_destructors,)*
+                        }
+                    }
                 }
 
                 #(#request_types)*
@@ -153,6 +608,36 @@ impl ToTokens for Type<&Interface> {
                     #(#event_variants,)*
                 }
 
+                impl Destructor for #event_enum {
+                    fn is_destructor(&self) -> bool {
+                        match *self {
+                            #(Self::#event_variant_names(_) => #event_destructors,)*
+                        }
+                    }
+                }
+
+                /// The opcodes of every request that destroys the object it is sent to
+                pub const DESTRUCTOR_REQUESTS: &[u16] = &[#(#destructor_requests),*];
+
+                /// The opcodes of every event that destroys the object it is sent from
+                pub const DESTRUCTOR_EVENTS: &[u16] = &[#(#destructor_events),*];
+
+                /// Returns the name of the request with `opcode`, as written in the protocol
+                pub fn request_name(opcode: u16) -> Option<&'static str> {
+                    match opcode {
+                        #(#request_opcodes => Some(#request_names),)*
+                        _ => None,
+                    }
+                }
+
+                /// Returns the name of the event with `opcode`, as written in the protocol
+                pub fn event_name(opcode: u16) -> Option<&'static str> {
+                    match opcode {
+                        #(#event_opcodes => Some(#event_names),)*
+                        _ => None,
+                    }
+                }
+
                 #(#event_types)*
 
                 #(#enum_types)*
@@ -173,7 +658,7 @@ impl ToTokens for Type<&Interface> {
                 impl Parser for #parser_enum {
                     type Output = #request_enum;
 
-                    fn parse(&mut self, bytes: impl Buffer<u8>, fds: impl Buffer<OwnedFd>) -> Option<Self::Output> {
+                    fn parse(&mut self, bytes: impl Buffer<u8>, fds: impl Buffer<OwnedFd>) -> Result<Self::Output, ParseError> {
                         match self {
                             #(#parser_parse,)*
                             _ => unreachable!(),
@@ -194,12 +679,93 @@ impl ToTokens for Type<&Request> {
         let ident = utils::ident(format!("{name}Request"));
         let args = self.0.args.iter().map(Type);
 
+        // only typed object arguments can be validated
+        let object_checks = self.0.args.iter().filter_map(|arg| {
+            let (ArgType::Object, Some(_)) = (&arg.ty, &arg.interface) else {
+                return None;
+            };
+
+            let name = utils::ident(&arg.name);
+            Some(match arg.allow_null {
+                false => quote! { map.check(self.#name)?; },
+                true => quote! {
+                    if self.#name.value() != 0 {
+                        map.check(self.#name)?;
+                    }
+                },
+            })
+        });
+
+        // typed ids inherit the version of the object the request was sent to,
+        // while untyped ids such as in wl_registry::bind carry their own interface and version
+        let new_id_inserts = self.0.args.iter().filter_map(|arg| {
+            let ArgType::NewId = arg.ty else {
+                return None;
+            };
+
+            let name = utils::ident(&arg.name);
+            Some(match arg.interface {
+                Some(_) => quote! { map.insert(self.#name, version, D::default())?; },
+                None => quote! {
+                    map.insert_by_name(
+                        self.#name.value,
+                        self.#name.name.to_string(),
+                        self.#name.version,
+                        D::default(),
+                    )?;
+                },
+            })
+        });
+
+        let wire_lens = self.0.args.iter().map(utils::wire_len);
+        let (clone_derive, clone_impl) = utils::clone(&ident, &self.0.args);
+        let fd_count = self
+            .0
+            .args
+            .iter()
+            .filter(|arg| matches!(arg.ty, ArgType::Fd))
+            .count();
+
         tokens.extend(quote! {
             #(#[doc = #docs])*
             #[derive(Debug)]
+            #clone_derive
             pub struct #ident {
                 #(#args)*
             }
+
+            #clone_impl
+
+            impl #ident {
+                /// The number of fds this request takes from the fd buffer when parsed
+                pub const FD_COUNT: usize = #fd_count;
+
+                /// Returns the number of bytes this request takes on the wire, including the header
+                ///
+                /// File descriptors are sent out of band and do not add to the length.
+                pub fn wire_len(&self) -> usize {
+                    8 #(+ #wire_lens)*
+                }
+
+                /// Ensures every typed object argument references a live object of the correct interface
+                #[allow(unused_variables)]
+                pub fn validate_objects<D>(&self, map: &ObjectMap<D>) -> Result<(), ObjectError> {
+                    #(#object_checks)*
+                    Ok(())
+                }
+
+                /// Registers every object created by a `new_id` argument of this request in `map`
+                ///
+                /// Objects with a known interface are created at `version`, which should be the version
+                /// of the object this request was sent to. Untyped ids carry their own interface and version.
+                ///
+                /// Fails without registering the remaining ids if one of them is already live.
+                #[allow(unused_variables)]
+                pub fn register_new_ids<D: Default>(&self, map: &mut ObjectMap<D>, version: u32) -> Result<(), ObjectError> {
+                    #(#new_id_inserts)*
+                    Ok(())
+                }
+            }
         });
     }
 }
@@ -214,16 +780,17 @@ impl ToTokens for Parser<&Request> {
             .args
             .iter()
             .map(|arg| match &arg.ty {
-                ArgType::Int => utils::ident("int"),
-                ArgType::Uint => utils::ident("uint"),
-                ArgType::Fixed => utils::ident("float"),
-                ArgType::String => utils::ident("string"),
-                ArgType::Object => utils::ident("uint"),
-                ArgType::Array => utils::ident("array"),
-                ArgType::Fd => utils::ident("fd"),
+                ArgType::Int => quote! { int::Parser },
+                ArgType::Uint => quote! { uint::Parser },
+                ArgType::Fixed => quote! { float::Parser },
+                ArgType::String if arg.allow_null => quote! { string::NullableParser },
+                ArgType::String => quote! { string::Parser },
+                ArgType::Object => quote! { uint::Parser },
+                ArgType::Array => quote! { array::Parser },
+                ArgType::Fd => quote! { fd::Parser },
                 ArgType::NewId => match &arg.interface {
-                    Some(_) => utils::ident("uint"),
-                    None => utils::ident("custom"),
+                    Some(_) => quote! { uint::Parser },
+                    None => quote! { custom::Parser },
                 },
             })
             .collect::<Box<[_]>>();
@@ -237,13 +804,13 @@ impl ToTokens for Parser<&Request> {
 
         tokens.extend(quote! {
             pub struct #parser {
-                #(#arg_name: Builder<#arg_ty::Parser>,)*
+                #(#arg_name: Builder<#arg_ty>,)*
             }
 
             impl #parser {
                 pub fn new() -> Self {
                     Self {
-                        #(#arg_name: Builder::new(#arg_ty::Parser::new()),)*
+                        #(#arg_name: Builder::new(<#arg_ty>::new()),)*
                     }
                 }
             }
@@ -255,10 +822,10 @@ impl ToTokens for Parser<&Request> {
                     &mut self,
                     mut bytes: impl Buffer<u8>,
                     mut fds: impl Buffer<OwnedFd>
-                ) -> Option<Self::Output> {
+                ) -> Result<Self::Output, ParseError> {
                     #(self.#arg_name.parse(&mut bytes, &mut fds)?;)*
 
-                    Some(#ident {
+                    Ok(#ident {
                         #(#arg_name: self.#arg_name.finish()?.into(),)*
                     })
                 }
@@ -273,12 +840,40 @@ impl ToTokens for Type<&Event> {
         let type_args = self.0.args.iter().map(Type);
         let docs = self.0.description.lines();
 
+        let wire_lens = self.0.args.iter().map(utils::wire_len);
+        let (clone_derive, clone_impl) = utils::clone(&ident, &self.0.args);
+        let arg_names = self
+            .0
+            .args
+            .iter()
+            .map(|arg| utils::ident(&arg.name))
+            .collect::<Box<[_]>>();
+        let arg_types = self.0.args.iter().map(utils::arg_type);
+
         tokens.extend(quote! {
             #(#[doc = #docs])*
             #[derive(Debug)]
+            #clone_derive
             pub struct #ident {
                 #(#type_args)*
             }
+
+            #clone_impl
+
+            impl #ident {
+                /// Returns a new event with its arguments in protocol order
+                #[allow(clippy::new_without_default, clippy::too_many_arguments)]
+                pub fn new(#(#arg_names: #arg_types),*) -> Self {
+                    Self { #(#arg_names),* }
+                }
+
+                /// Returns the number of bytes this event takes on the wire, including the header
+                ///
+                /// File descriptors are sent out of band and do not add to the length.
+                pub fn wire_len(&self) -> usize {
+                    8 #(+ #wire_lens)*
+                }
+            }
         });
     }
 }
@@ -292,13 +887,30 @@ impl ToTokens for Type<&Enum> {
         let entry_docs = entry_iter.clone().map(|entry| &entry.summary);
         let entry_names = entry_iter
             .clone()
-            .map(|entry| utils::ident(&entry.name.to_case(Case::Pascal)))
+            .map(|entry| utils::ident(entry.name.to_case(Case::Pascal)))
+            .collect::<Box<[_]>>();
+        let entry_values = entry_iter
+            .clone()
+            .map(|entry| entry.value)
+            .collect::<Box<[_]>>();
+        let entry_since = entry_iter
+            .map(|entry| entry.since.unwrap_or(1))
             .collect::<Box<[_]>>();
-        let entry_values = entry_iter.map(|entry| entry.value).collect::<Box<[_]>>();
 
+        let bitfield_impl = self.0.bitfield.then(|| {
+            let known_bits = entry_values.iter().fold(0, |bits, value| bits | value);
+            quote! {
+                impl Bitfield for #ident {
+                    const KNOWN_BITS: u32 = #known_bits;
+                }
+            }
+        });
+
+        // the entries are plain values, so they are Clone and Copy for the structs that hold them
+        // and for Bitfield, and comparable so parsed values can be matched against known entries
         tokens.extend(quote! {
             #(#[doc = #docs])*
-            #[derive(Debug)]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
             #[repr(u32)]
             pub enum #ident {
                 #(
@@ -316,25 +928,124 @@ impl ToTokens for Type<&Enum> {
                     })
                 }
             }
+
+            impl From<#ident> for u32 {
+                fn from(value: #ident) -> Self {
+                    value as u32
+                }
+            }
+
+            impl #ident {
+                /// Returns the interface version this entry was added in
+                pub const fn since(self) -> u32 {
+                    match self {
+                        #(Self::#entry_names => #entry_since,)*
+                    }
+                }
+
+                /// Returns the interface version the entry with `value` was added in
+                ///
+                /// This can be used to reject values that are newer than the version of the object
+                /// they were sent to, which is a protocol violation.
+                ///
+                /// Returns `None` if `value` is not a known entry
+                pub const fn entry_since(value: u32) -> Option<u32> {
+                    match value {
+                        #(#entry_values => Some(#entry_since),)*
+                        _ => None,
+                    }
+                }
+            }
+
+            #bitfield_impl
         });
     }
 }
 
 impl ToTokens for Type<&Arg> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let docs = &self.0.summary;
+        let docs = self.0.doc_lines();
         let ident = utils::ident(&self.0.name);
 
-        let interface = self
-            .0
+        let arg_ty = utils::arg_type(self.0);
+
+        // only strings are optional, so document how other null values are represented
+        let null_doc = match (self.0.allow_null, &self.0.ty) {
+            (false, _) => None,
+            (true, ArgType::String) => Some("This may be null, which is represented by `None`."),
+            (true, _) => Some("This may be null, which is represented by an id of `0`."),
+        };
+        let null_doc = null_doc.map(|doc| quote! { #[doc = ""] #[doc = #doc] });
+
+        tokens.extend(quote! {
+            #(#[doc = #docs])*
+            #null_doc
+            #ident: #arg_ty,
+        });
+    }
+}
+
+impl ToTokens for Parser<&ArgType> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let ty = match &self.0 {
+            ArgType::Int => utils::ident("int"),
+            ArgType::Uint => utils::ident("uint"),
+            ArgType::Fixed => utils::ident("float"),
+            ArgType::String => utils::ident("string"),
+            ArgType::Object => utils::ident("uint"),
+            ArgType::NewId => utils::ident("uint"),
+            ArgType::Array => utils::ident("array"),
+            ArgType::Fd => utils::ident("fd"),
+        };
+
+        tokens.extend(quote! { #ty })
+    }
+}
+
+mod utils {
+    use convert_case::{Case, Casing};
+    use proc_macro2::{Span, TokenStream};
+    use quote::{ToTokens, quote};
+    use syn::Ident;
+
+    use super::{Arg, ArgType};
+
+    pub fn ident(s: impl AsRef<str>) -> Ident {
+        // names such as hyphenated protocol names may contain invalid identifier characters
+        let s = &*s
+            .as_ref()
+            .replace(|c: char| !c.is_alphanumeric() && c != '_', "_");
+        if s.starts_with(|c: char| c.is_numeric()) {
+            return Ident::new(&format!("_{s}"), Span::call_site());
+        }
+
+        // keywords fail to parse as plain identifiers
+        if syn::parse_str::<Ident>(s).is_ok() {
+            return Ident::new(s, Span::call_site());
+        }
+
+        // most keywords can be used as raw identifiers, but the path keywords cannot
+        match s {
+            "self" | "Self" | "super" | "crate" | "_" => {
+                Ident::new(&format!("{s}_"), Span::call_site())
+            }
+            _ => Ident::new_raw(s, Span::call_site()),
+        }
+    }
+
+    /// Returns the rust type used to store `arg`
+    pub fn arg_type(arg: &Arg) -> TokenStream {
+        let interface = arg
             .interface
             .as_ref()
-            .map(|i| utils::ident(i.to_case(Case::Pascal)));
+            .map(|i| ident(i.to_case(Case::Pascal)));
 
-        let mut arg_ty = match &self.0.ty {
+        let mut arg_ty = match &arg.ty {
             ArgType::Int => quote! { i32 },
+            ArgType::Uint if arg.global_name => quote! { GlobalName },
             ArgType::Uint => quote! { u32 },
             ArgType::Fixed => quote! { f32 },
+            ArgType::String if arg.allow_null => quote! { Option<RawString> },
             ArgType::String => quote! { RawString },
             ArgType::Array => quote! { Box<[u8]> },
             ArgType::Fd => quote! { OwnedFd },
@@ -348,54 +1059,68 @@ impl ToTokens for Type<&Arg> {
             },
         };
 
-        if let Some(kind) = &self.0.enum_kind {
+        if let Some(kind) = &arg.enum_kind {
             let mut parts = kind.split(".");
             let first = parts.next().unwrap();
             let kind = match parts.next() {
-                None => utils::ident(first.to_case(Case::Pascal)).into_token_stream(),
+                None => ident(first.to_case(Case::Pascal)).into_token_stream(),
                 Some(second) => {
-                    let interface = utils::ident(first);
-                    let ident = utils::ident(second.to_case(Case::Pascal));
+                    let interface = ident(first);
+                    let ident = ident(second.to_case(Case::Pascal));
                     quote! { #interface::#ident }
                 }
             };
 
-            arg_ty = quote! { RawEnum<#arg_ty, #kind> };
+            arg_ty = match arg.bitfield {
+                true => quote! { Flags<#kind> },
+                false => quote! { RawEnum<#arg_ty, #kind> },
+            };
         }
 
-        tokens.extend(quote! {
-            #[doc = #docs]
-            #ident: #arg_ty,
-        });
+        arg_ty
     }
-}
 
-impl ToTokens for Parser<&ArgType> {
-    fn to_tokens(&self, tokens: &mut TokenStream) {
-        let ty = match &self.0 {
-            ArgType::Int => utils::ident("int"),
-            ArgType::Uint => utils::ident("uint"),
-            ArgType::Fixed => utils::ident("float"),
-            ArgType::String => utils::ident("string"),
-            ArgType::Object => utils::ident("uint"),
-            ArgType::NewId => utils::ident("uint"),
-            ArgType::Array => utils::ident("array"),
-            ArgType::Fd => utils::ident("fd"),
-        };
-
-        tokens.extend(quote! { #ty })
+    /// Returns an expression for the number of bytes `arg` takes on the wire
+    pub fn wire_len(arg: &Arg) -> TokenStream {
+        let name = ident(&arg.name);
+        match (&arg.ty, &arg.interface) {
+            (ArgType::String, _) if arg.allow_null => {
+                quote! { RawString::nullable_wire_len(self.#name.as_ref()) }
+            }
+            (ArgType::String, _) | (ArgType::NewId, None) => quote! { self.#name.wire_len() },
+            (ArgType::Array, _) => quote! { 4 + ((self.#name.len() + 3) & !3) },
+            (ArgType::Fd, _) => quote! { 0 },
+            _ => quote! { 4 },
+        }
     }
-}
 
-mod utils {
-    use proc_macro2::Span;
-    use syn::Ident;
-
-    pub fn ident(s: impl AsRef<str>) -> Ident {
-        let s = s.as_ref();
-        match s.starts_with(|c: char| c.is_numeric()) {
-            true => Ident::new(&format!("_{s}"), Span::call_site()),
-            false => Ident::new(s, Span::call_site()),
+    /// Returns a `Clone` derive for a struct with `args`, and an extra impl block
+    ///
+    /// `OwnedFd` is not `Clone`, so structs with fd arguments get a `try_clone` that dups each fd instead.
+    pub fn clone(struct_ident: &Ident, args: &[Arg]) -> (TokenStream, TokenStream) {
+        if !args.iter().any(|arg| matches!(arg.ty, ArgType::Fd)) {
+            return (quote! { #[derive(Clone)] }, TokenStream::new());
         }
+
+        let fields = args.iter().map(|arg| {
+            let name = ident(&arg.name);
+            match arg.ty {
+                ArgType::Fd => quote! { #name: self.#name.try_clone()? },
+                _ => quote! { #name: self.#name.clone() },
+            }
+        });
+
+        let clone_impl = quote! {
+            impl #struct_ident {
+                /// Clones this struct, duplicating every file descriptor it holds
+                pub fn try_clone(&self) -> ::std::io::Result<Self> {
+                    Ok(Self {
+                        #(#fields,)*
+                    })
+                }
+            }
+        };
+
+        (TokenStream::new(), clone_impl)
     }
 }
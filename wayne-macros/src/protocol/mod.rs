@@ -1,4 +1,4 @@
 pub mod generator;
 pub mod xml;
 
-pub use generator::Generator;
+pub use generator::{Generator, StrGenerator};
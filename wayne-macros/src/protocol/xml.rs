@@ -5,10 +5,57 @@ use serde::Deserialize;
 pub struct Protocol {
     #[serde(rename = "@name")]
     pub name: String,
-    #[serde(rename = "interface")]
+    #[serde(default, rename = "interface")]
     pub interfaces: Vec<Interface>,
 }
 
+/// The `uint` arguments that hold global names, as `(interface, arg)` pairs
+///
+/// The protocol xml has no way to mark an argument as a global name, so this is a special case
+/// for the core protocol, where only `wl_registry` uses them. The `name` arguments of every other
+/// interface are left as plain integers.
+const GLOBAL_NAME_ARGS: &[(&str, &str)] = &[("wl_registry", "name")];
+
+impl Protocol {
+    /// Marks every argument that references a bitfield enum from this protocol
+    ///
+    /// Enums from other protocols cannot be resolved, so arguments referencing them are left unmarked.
+    pub fn mark_bitfields(&mut self) {
+        let bitfields = self
+            .interfaces
+            .iter()
+            .flat_map(|interface| {
+                let bitfields = interface.enums.iter().filter(|e| e.bitfield);
+                bitfields.map(|e| format!("{}.{}", interface.name, e.name))
+            })
+            .collect::<Vec<_>>();
+
+        for interface in &mut self.interfaces {
+            let request_args = interface
+                .requests
+                .iter_mut()
+                .flat_map(|request| &mut request.args);
+            let event_args = interface
+                .events
+                .iter_mut()
+                .flat_map(|event| &mut event.args);
+            for arg in request_args.chain(event_args) {
+                let Some(kind) = &arg.enum_kind else {
+                    continue;
+                };
+
+                // enums in the same interface are referenced without the interface prefix
+                let kind = match kind.contains('.') {
+                    true => kind.clone(),
+                    false => format!("{}.{kind}", interface.name),
+                };
+
+                arg.bitfield = bitfields.contains(&kind);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default, Deserialize)]
 pub struct Description {
     #[serde(default, rename = "@summary")]
@@ -43,10 +90,45 @@ pub struct Interface {
     pub enums: Vec<Enum>,
 }
 
+impl Interface {
+    /// Returns the names of every interface referenced by an argument or enum of this interface
+    pub fn references(&self) -> impl Iterator<Item = &str> {
+        let request_args = self.requests.iter().flat_map(|request| &request.args);
+        let event_args = self.events.iter().flat_map(|event| &event.args);
+        request_args.chain(event_args).flat_map(|arg| {
+            let interface = arg.interface.as_deref();
+            let enum_interface = arg
+                .enum_kind
+                .as_deref()
+                .and_then(|kind| Some(kind.split_once('.')?.0));
+            interface.into_iter().chain(enum_interface)
+        })
+    }
+
+    /// Marks the arguments listed in [`GLOBAL_NAME_ARGS`], which hold global names instead of plain integers
+    pub fn mark_global_names(&mut self) {
+        let request_args = self
+            .requests
+            .iter_mut()
+            .flat_map(|request| &mut request.args);
+        let event_args = self.events.iter_mut().flat_map(|event| &mut event.args);
+        for arg in request_args.chain(event_args) {
+            let listed = GLOBAL_NAME_ARGS.contains(&(self.name.as_str(), arg.name.as_str()));
+            arg.global_name = listed && matches!(arg.ty, ArgType::Uint);
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Request {
     #[serde(rename = "@name")]
     pub name: String,
+    #[serde(
+        default,
+        rename = "@type",
+        deserialize_with = "utils::parse_destructor"
+    )]
+    pub destructor: bool,
     #[serde(default, rename = "description")]
     pub description: Description,
     #[serde(default, rename = "arg")]
@@ -63,14 +145,37 @@ pub struct Arg {
     pub interface: Option<String>,
     #[serde(rename = "@enum")]
     pub enum_kind: Option<String>,
-    // #[serde(
-    //     default,
-    //     rename = "@allow-null",
-    //     deserialize_with = "utils::parse_bool"
-    // )]
-    // pub allow_null: bool,
+    #[serde(
+        default,
+        rename = "@allow-null",
+        deserialize_with = "utils::parse_bool"
+    )]
+    pub allow_null: bool,
     #[serde(default, rename = "@summary")]
     pub summary: String,
+    #[serde(rename = "description")]
+    pub description: Option<Description>,
+    /// Set for arguments holding a global name, see [`Interface::mark_global_names`]
+    #[serde(skip)]
+    pub global_name: bool,
+    /// Set for arguments referencing a bitfield enum, see [`Protocol::mark_bitfields`]
+    #[serde(skip)]
+    pub bitfield: bool,
+}
+
+impl Arg {
+    /// Returns the lines of the nested description if there is one, otherwise the summary
+    pub fn doc_lines(&self) -> impl Iterator<Item = &str> {
+        let description = self
+            .description
+            .as_ref()
+            .filter(|d| d.text.is_some() || !d.summary.is_empty());
+        let summary = description.is_none().then_some(self.summary.as_str());
+        description
+            .into_iter()
+            .flat_map(Description::lines)
+            .chain(summary)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,6 +195,12 @@ pub enum ArgType {
 pub struct Event {
     #[serde(rename = "@name")]
     pub name: String,
+    #[serde(
+        default,
+        rename = "@type",
+        deserialize_with = "utils::parse_destructor"
+    )]
+    pub destructor: bool,
     #[serde(default, rename = "description")]
     pub description: Description,
     #[serde(default, rename = "arg")]
@@ -100,6 +211,8 @@ pub struct Event {
 pub struct Enum {
     #[serde(rename = "@name")]
     pub name: String,
+    #[serde(default, rename = "@bitfield", deserialize_with = "utils::parse_bool")]
+    pub bitfield: bool,
     #[serde(default, rename = "description")]
     pub description: Description,
     #[serde(rename = "entry")]
@@ -114,22 +227,35 @@ pub struct Entry {
     pub value: u32,
     #[serde(default, rename = "@summary")]
     pub summary: String,
+    /// The interface version this entry was added in, which defaults to `1`
+    #[serde(rename = "@since")]
+    pub since: Option<u32>,
 }
 
 mod utils {
     use serde::{Deserialize, Deserializer};
 
-    // pub fn parse_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
-    //     let string = String::deserialize(deserializer)?;
-    //     match string.as_str() {
-    //         "true" => Ok(true),
-    //         _ => Ok(false),
-    //     }
-    // }
+    pub fn parse_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        match string.as_str() {
+            "true" => Ok(true),
+            _ => Ok(false),
+        }
+    }
+
+    pub fn parse_destructor<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Ok(string == "destructor")
+    }
 
     pub fn parse_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
         let string = String::deserialize(deserializer)?;
         match string.starts_with("0x") {
+            // negative values are stored using their bit pattern on the wire
+            false if string.starts_with('-') => string
+                .parse::<i32>()
+                .map(|value| value as u32)
+                .map_err(|err| serde::de::Error::custom(err.to_string())),
             false => string
                 .parse::<u32>()
                 .map_err(|err| serde::de::Error::custom(err.to_string())),
@@ -1,19 +1,128 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, os::fd::OwnedFd};
 
 pub trait Buffer<T> {
     fn take(&mut self) -> Option<T>;
+
+    /// Returns the number of items left in the buffer, if it is known
+    ///
+    /// This is used to check that a request took every fd that was sent with it.
+    fn available(&self) -> Option<usize> {
+        None
+    }
 }
 
-impl<T, B: Buffer<T>> Buffer<T> for &mut B {
+impl<T, B: Buffer<T> + ?Sized> Buffer<T> for &mut B {
     fn take(&mut self) -> Option<T> {
         B::take(self)
     }
+
+    fn available(&self) -> Option<usize> {
+        B::available(self)
+    }
 }
 
 impl<T> Buffer<T> for VecDeque<T> {
     fn take(&mut self) -> Option<T> {
         self.pop_front()
     }
+
+    fn available(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+/// A [`Buffer`] that takes bytes from a slice, such as the body of a message
+///
+/// The slice is consumed from the front, so a single `&mut SliceBuffer`
+/// can be passed to several parsers in sequence.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SliceBuffer<'a> {
+    slice: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SliceBuffer<'a> {
+    /// Returns a new buffer that takes bytes from `slice`
+    pub const fn new(slice: &'a [u8]) -> Self {
+        Self { slice, position: 0 }
+    }
+
+    /// Returns the number of bytes that have been taken
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the bytes that have not been taken yet
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.slice[self.position..]
+    }
+
+    /// Returns `true` if every byte has been taken
+    pub const fn is_empty(&self) -> bool {
+        self.position >= self.slice.len()
+    }
+}
+
+impl Buffer<u8> for SliceBuffer<'_> {
+    fn take(&mut self) -> Option<u8> {
+        let byte = *self.slice.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn available(&self) -> Option<usize> {
+        Some(self.slice.len().saturating_sub(self.position))
+    }
+}
+
+/// A [`Buffer`] of owned fds, such as the fds received alongside a message
+///
+/// Fds are taken from the front, in the order they were received.
+#[derive(Debug, Default)]
+pub struct VecFdBuffer(VecDeque<OwnedFd>);
+
+impl VecFdBuffer {
+    /// Returns a new empty fd buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues `fd` at the back of the buffer
+    pub fn push(&mut self, fd: OwnedFd) {
+        self.0.push_back(fd);
+    }
+
+    /// Returns the number of fds in the buffer
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if there are no fds in the buffer
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Buffer<OwnedFd> for VecFdBuffer {
+    fn take(&mut self) -> Option<OwnedFd> {
+        self.0.pop_front()
+    }
+
+    fn available(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+impl From<Vec<OwnedFd>> for VecFdBuffer {
+    fn from(fds: Vec<OwnedFd>) -> Self {
+        Self(fds.into())
+    }
+}
+
+impl FromIterator<OwnedFd> for VecFdBuffer {
+    fn from_iter<I: IntoIterator<Item = OwnedFd>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
 }
 
 pub struct IterBuf<I>(I);
@@ -22,6 +131,14 @@ impl<T, I: Iterator<Item = T>> Buffer<T> for IterBuf<I> {
     fn take(&mut self) -> Option<T> {
         self.0.next()
     }
+
+    fn available(&self) -> Option<usize> {
+        // only an exact size hint gives the number of items left
+        match self.0.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        }
+    }
 }
 
 impl<T: Iterator> IterExt for T {}
@@ -33,3 +150,52 @@ pub trait IterExt: Iterator {
         IterBuf(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::{fd::AsRawFd, unix::net::UnixStream};
+
+    use crate::{
+        Parser,
+        parse::{fd, string, uint},
+    };
+
+    use super::*;
+
+    #[test]
+    fn parse_from_slice() {
+        let mut body = 42u32.to_ne_bytes().to_vec();
+        body.extend(4u32.to_ne_bytes());
+        body.extend(b"abc\0");
+
+        let mut bytes = SliceBuffer::new(&body);
+        let mut fds = VecDeque::new();
+
+        let value = uint::Parser::new().parse(&mut bytes, &mut fds).unwrap();
+        assert_eq!(value, 42);
+        assert_eq!(bytes.position(), 4);
+
+        let string = string::Parser::new().parse(&mut bytes, &mut fds).unwrap();
+        assert_eq!(string.to_string(), "abc");
+        assert!(bytes.is_empty());
+        assert!(bytes.remaining().is_empty());
+    }
+
+    #[test]
+    fn parse_from_fd_buffer() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let raw = [a.as_raw_fd(), b.as_raw_fd()];
+
+        let mut fds = VecFdBuffer::from(vec![OwnedFd::from(a), OwnedFd::from(b)]);
+        let mut bytes = SliceBuffer::new(&[]);
+        assert_eq!(fds.len(), 2);
+
+        // fds should be taken in the order they were queued
+        for raw in raw {
+            let fd = fd::Parser::new().parse(&mut bytes, &mut fds).unwrap();
+            assert_eq!(fd.as_raw_fd(), raw);
+        }
+
+        assert!(fds.is_empty());
+    }
+}
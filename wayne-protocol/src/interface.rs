@@ -0,0 +1,63 @@
+use std::os::fd::OwnedFd;
+
+use crate::{Buffer, ParseError};
+
+/// A wayland interface generated from a protocol file
+pub trait Interface {
+    /// The name of the interface as it appears on the wire
+    const NAME: &'static str;
+
+    /// The latest version of the interface that was generated
+    const VERSION: u32;
+}
+
+/// An [`Interface`] whose requests can be parsed
+///
+/// This is implemented for every generated interface,
+/// and can be used as a bound to parse requests for any interface generically.
+pub trait RequestParser: Interface {
+    /// The request enum for the interface
+    type Request;
+
+    /// The number of requests on the interface, which is one more than the largest opcode
+    const REQUEST_COUNT: u16;
+
+    /// Parses a complete request with `opcode` from a message `body`
+    ///
+    /// Any fds the request carries are taken from `fds`.
+    fn parse_request(
+        opcode: u16,
+        body: &[u8],
+        fds: &mut impl Buffer<OwnedFd>,
+    ) -> Result<Self::Request, ParseError>;
+}
+
+/// Parses a complete request with `opcode` for the interface `I` from a message `body`
+///
+/// This is the same as [`RequestParser::parse_request`].
+pub fn parse_request<I: RequestParser>(
+    opcode: u16,
+    body: &[u8],
+    fds: &mut impl Buffer<OwnedFd>,
+) -> Result<I::Request, ParseError> {
+    I::parse_request(opcode, body, fds)
+}
+
+/// Parses a complete request with `opcode` for the interface `I`, checking the opcode first
+///
+/// Unlike [`parse_request`], an out of range opcode is rejected from [`RequestParser::REQUEST_COUNT`]
+/// before looking up a parser, returning [`ParseError::UnknownOpcode`] with the interface name.
+pub fn parse_request_checked<I: RequestParser>(
+    opcode: u16,
+    body: &[u8],
+    fds: &mut impl Buffer<OwnedFd>,
+) -> Result<I::Request, ParseError> {
+    if opcode >= I::REQUEST_COUNT {
+        return Err(ParseError::UnknownOpcode {
+            interface: I::NAME,
+            opcode,
+        });
+    }
+
+    I::parse_request(opcode, body, fds)
+}
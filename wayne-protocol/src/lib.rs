@@ -1,9 +1,15 @@
 pub mod buffer;
+pub mod interface;
 pub mod macros;
+pub mod object;
 pub mod parse;
 pub mod parser;
 pub mod protocols;
+pub mod registry;
 pub mod types;
 
 pub use buffer::Buffer;
-pub use parser::Parser;
+pub use interface::{Interface, RequestParser, parse_request, parse_request_checked};
+pub use object::{Destructor, ObjectMap};
+pub use parser::{BoxedParser, DynParser, ParseError, Parser, parse_all, parse_with_rest};
+pub use registry::Registry;
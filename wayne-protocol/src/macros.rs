@@ -1,15 +1,259 @@
 // re-export macro impls as hidden
 #[doc(hidden)]
 pub mod __impl {
-    pub use wayne_macros::protocol;
+    pub use wayne_macros::{protocol, protocol_str};
 }
 
 /// Takes a path to a wayland protocol xml file, and generates the associated rust structrues.
 ///
 /// The xml path is relative to your crates root directory.
+///
+/// Paths to already generated protocol modules may follow the xml path.
+/// Their interfaces are imported so that cross-protocol references resolve,
+/// such as `xdg_shell` referencing `wl_surface` from the core protocol.
+///
+/// An `only = ["wl_display", "wl_registry"]` option may also follow, which generates only the
+/// listed interfaces to reduce compile times. Every interface from the same protocol that a
+/// listed interface references must also be listed.
 #[macro_export]
 macro_rules! generate {
-    ($path:literal) => {
-        $crate::macros::__impl::protocol!($crate, $path);
+    ($path:literal $(, $($options:tt)*)?) => {
+        $crate::macros::__impl::protocol!($crate, $path $(, $($options)*)?);
     };
 }
+
+/// Takes a string literal containing wayland protocol xml, and generates the associated rust structures.
+///
+/// This is useful for prototyping small protocols and testing generated code without an xml file.
+///
+/// The same options as [`generate`] may follow the xml.
+#[macro_export]
+macro_rules! generate_str {
+    ($xml:literal $(, $($options:tt)*)?) => {
+        $crate::macros::__impl::protocol_str!($crate, $xml $(, $($options)*)?);
+    };
+}
+
+/// Parses a complete message body into a tuple of argument types, in order
+///
+/// Each type must implement [`parse::Arg`](crate::parse::Arg), which covers the basic wire types.
+/// This is a lighter alternative to generated requests for ad-hoc parsing in tests and tools.
+///
+/// Like [`parse_all`](crate::parse_all), the body must hold exactly the listed arguments.
+/// For example `parse_args!(&body, &mut fds => u32, RawString)` returns a `(u32, RawString)`.
+#[macro_export]
+macro_rules! parse_args {
+    ($body:expr, $fds:expr => $($ty:ty),+ $(,)?) => {{
+        let mut bytes = $crate::buffer::SliceBuffer::new($body);
+        let mut fds = $fds;
+        (|| -> ::std::result::Result<_, $crate::ParseError> {
+            let output = ($(
+                $crate::Parser::parse(
+                    &mut <$ty as $crate::parse::Arg>::parser(),
+                    &mut bytes,
+                    &mut fds,
+                )?,
+            )+);
+
+            match bytes.remaining().len() {
+                0 => Ok(output),
+                len => Err($crate::ParseError::TrailingBytes(len)),
+            }
+        })()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, buffer::IterExt};
+
+    crate::generate_str!(
+        r#"
+        <protocol name="inline">
+          <interface name="inline_counter" version="2">
+            <request name="add">
+              <arg name="amount" type="uint">
+                <description summary="the amount to add">
+                  The amount to add to the counter.
+                  Adding past the maximum value wraps around.
+                </description>
+              </arg>
+            </request>
+          </interface>
+        </protocol>
+        "#
+    );
+
+    crate::generate_str!(
+        r#"
+        <protocol name="inline-keywords">
+          <interface name="inline_match" version="1">
+            <request name="type">
+              <arg name="type" type="uint"/>
+              <arg name="self" type="int"/>
+              <arg name="fn" type="string"/>
+            </request>
+            <enum name="match">
+              <entry name="loop" value="0"/>
+              <entry name="Self" value="1"/>
+            </enum>
+          </interface>
+        </protocol>
+        "#
+    );
+
+    // the hyphenated protocol name is generated as `inline_keywords`
+    #[test]
+    fn generate_keywords() {
+        let mut bytes = [1u32, 2, 3].map(u32::to_ne_bytes).concat();
+        bytes.extend(b"fn\0\0");
+
+        let mut parser = inline_keywords::parser_for("inline_match", 0).unwrap();
+        let request = parser
+            .parse(bytes.into_iter().buffer(), std::iter::empty().buffer())
+            .unwrap();
+        assert!(matches!(request, inline_keywords::Request::InlineMatch(_)));
+    }
+
+    crate::generate_str!(
+        r#"
+        <protocol name="inline_shell">
+          <interface name="inline_surface" version="1">
+            <request name="wrap">
+              <arg name="surface" type="object" interface="wl_surface"/>
+            </request>
+          </interface>
+        </protocol>
+        "#,
+        crate::protocols::wayland,
+    );
+
+    #[test]
+    fn generate_extern() {
+        let mut map = crate::ObjectMap::new();
+        map.insert_by_name(3, "wl_surface", 6, ()).unwrap();
+
+        let mut parser = inline_shell::parser_for("inline_surface", 0).unwrap();
+        let bytes = 3u32.to_ne_bytes().into_iter().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        assert_eq!(request.validate_objects(&map), Ok(()));
+    }
+
+    #[test]
+    fn generate_inline() {
+        assert_eq!(inline::PROTOCOL_NAME, "inline");
+        assert_eq!(inline::inline_counter::VERSION, 2);
+
+        let mut parser = inline::parser_for("inline_counter", 0).unwrap();
+        let bytes = 5u32.to_ne_bytes().into_iter().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        assert!(matches!(request, inline::Request::InlineCounter(_)));
+    }
+
+    crate::generate_str!(
+        r#"
+        <protocol name="inline_names">
+          <interface name="inline_output" version="1">
+            <request name="rename">
+              <arg name="name" type="uint"/>
+            </request>
+            <event name="renamed">
+              <arg name="name" type="uint"/>
+            </event>
+          </interface>
+        </protocol>
+        "#
+    );
+
+    // only the name args of wl_registry are global names
+    #[test]
+    fn plain_name_args() {
+        use inline_names::inline_output::{InlineOutputRequest, RenamedEvent};
+
+        let event = RenamedEvent::new(7u32);
+        assert_eq!(event.wire_len(), 8 + 4);
+
+        let body = 7u32.to_ne_bytes();
+        let request = InlineOutputRequest::parse(0, &body, &mut std::iter::empty().buffer());
+        assert!(format!("{:?}", request.unwrap()).contains("name: 7"));
+    }
+
+    // xdg-shell mixes exclusive enums and a bitfield, and refers to interfaces from wayland.xml
+    mod xdg {
+        crate::generate!("protocol/xdg-shell.xml", crate::protocols::wayland);
+    }
+
+    #[test]
+    fn parse_positioner_enums() {
+        use xdg::xdg_shell::xdg_positioner::{
+            Anchor, ConstraintAdjustment, Gravity, XdgPositionerRequest,
+        };
+
+        let parse = |opcode, value: u32| {
+            let body = value.to_ne_bytes();
+            let request =
+                XdgPositionerRequest::parse(opcode, &body, &mut std::iter::empty().buffer());
+            format!("{:?}", request.unwrap())
+        };
+
+        // anchor and gravity are exclusive enums, so only a single entry is valid
+        assert!(parse(3, 7).contains("SetAnchor"));
+        assert!(parse(4, 6).contains("SetGravity"));
+        assert_eq!(Anchor::try_from(7), Ok(Anchor::TopRight));
+        assert_eq!(Gravity::try_from(9), Err(()));
+
+        // constraint_adjustment is a bitfield, so combined entries and unknown bits are kept
+        let request = parse(5, 1 | 8 | 64);
+        assert!(
+            request.contains("known: 0x9, unknown_bits: 0x40"),
+            "{request}"
+        );
+        assert_eq!(
+            <ConstraintAdjustment as crate::types::Bitfield>::KNOWN_BITS,
+            0x3f
+        );
+    }
+
+    mod display_only {
+        crate::generate!(
+            "protocol/wayland.xml",
+            only = ["wl_display", "wl_registry", "wl_callback"],
+        );
+    }
+
+    #[test]
+    fn generate_only() {
+        use display_only::wayland;
+
+        assert_eq!(wayland::INTERFACE_COUNT, 3);
+        assert!(wayland::parser_for("wl_registry", 0).is_some());
+        assert!(wayland::parser_for("wl_compositor", 0).is_none());
+    }
+
+    #[test]
+    fn parse_args() {
+        use std::os::{fd::OwnedFd, unix::net::UnixStream};
+
+        use crate::{ParseError, buffer::VecFdBuffer, types::RawString};
+
+        let (fd, _) = UnixStream::pair().unwrap();
+        let mut fds = VecFdBuffer::from(vec![OwnedFd::from(fd)]);
+        let mut body = (-3i32).to_ne_bytes().to_vec();
+        body.extend(RawString::from("abc").to_wire());
+        body.extend(256u32.to_ne_bytes());
+
+        let (int, string, _fd, fixed) =
+            crate::parse_args!(&body, &mut fds => i32, RawString, OwnedFd, f32).unwrap();
+        assert_eq!(
+            (int, string.to_string(), fixed),
+            (-3, "abc".to_string(), 1.0)
+        );
+        assert!(fds.is_empty());
+
+        let result = crate::parse_args!(&body[..4], &mut fds => i32, u32);
+        assert_eq!(result.unwrap_err(), ParseError::Incomplete);
+
+        let result = crate::parse_args!(&body, &mut fds => i32);
+        assert_eq!(result.unwrap_err(), ParseError::TrailingBytes(12));
+    }
+}
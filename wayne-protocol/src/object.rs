@@ -0,0 +1,438 @@
+use std::collections::{HashMap, hash_map::Entry};
+
+use thiserror::Error;
+
+use crate::{
+    Interface,
+    types::id::{NewId, ObjectId},
+};
+
+/// An error produced when an object id does not reference a valid object
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ObjectError {
+    #[error("Object {_0} does not exist")]
+    Missing(u32),
+    #[error("Object {_0} already exists")]
+    AlreadyExists(u32),
+    #[error("Object {id} is a '{found}' but expected a '{expected}'")]
+    Mismatch {
+        id: u32,
+        expected: &'static str,
+        found: Box<str>,
+    },
+}
+
+/// Implemented by requests and events that may destroy the object they belong to
+pub trait Destructor {
+    /// Returns `true` if this message destroys the object it belongs to
+    fn is_destructor(&self) -> bool;
+}
+
+/// The interface information and user data for a live protocol object
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Object<D = ()> {
+    pub interface: Box<str>,
+    pub version: u32,
+    pub data: D,
+}
+
+/// A map of live object ids to the interface each object implements
+///
+/// This is used to route incoming messages to the correct request parser.
+/// Each object also stores user data `D`, such as the state a compositor keeps for it,
+/// so the map can act as the central object store instead of a parallel map keyed by the same ids.
+#[derive(Debug)]
+pub struct ObjectMap<D = ()> {
+    objects: HashMap<u32, Object<D>>,
+}
+
+impl<D> Default for ObjectMap<D> {
+    fn default() -> Self {
+        Self {
+            objects: HashMap::new(),
+        }
+    }
+}
+
+impl<D> ObjectMap<D> {
+    /// Returns a new empty object map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of live objects in the map
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns `true` if there are no live objects in the map
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Records the new object `id` as implementing the interface `T` at `version`
+    ///
+    /// Clients must never reuse an id that is still live, so this fails if `id` already exists.
+    /// The dispatcher should then report an `invalid_object` error to the client.
+    pub fn insert<T: Interface>(
+        &mut self,
+        id: NewId<T>,
+        version: u32,
+        data: D,
+    ) -> Result<(), ObjectError> {
+        self.insert_by_name(id.value(), T::NAME, version, data)
+    }
+
+    /// Records the new object `id` as implementing the interface named `interface` at `version`
+    ///
+    /// This is used when the interface is only known by its name at runtime,
+    /// such as objects created through an untyped `new_id` in `wl_registry::bind`.
+    /// Like [`insert`](Self::insert), this fails if `id` already exists.
+    pub fn insert_by_name(
+        &mut self,
+        id: u32,
+        interface: impl Into<Box<str>>,
+        version: u32,
+        data: D,
+    ) -> Result<(), ObjectError> {
+        match self.objects.entry(id) {
+            Entry::Occupied(_) => Err(ObjectError::AlreadyExists(id)),
+            Entry::Vacant(entry) => {
+                entry.insert(Object {
+                    interface: interface.into(),
+                    version,
+                    data,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the object stored at `id`
+    pub fn get(&self, id: u32) -> Option<&Object<D>> {
+        self.objects.get(&id)
+    }
+
+    /// Returns a mutable reference to the user data of the object stored at `id`
+    ///
+    /// Only the data is mutable, as the interface and version of a live object never change.
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut D> {
+        self.objects.get_mut(&id).map(|object| &mut object.data)
+    }
+
+    /// Removes and returns the object stored at `id`
+    pub fn remove(&mut self, id: u32) -> Option<Object<D>> {
+        self.objects.remove(&id)
+    }
+
+    /// Completes a `request` that was sent to object `id`
+    ///
+    /// If the request is a destructor, the object is removed from the map and returned,
+    /// so the caller can deterministically release any fd-backed state associated with it.
+    ///
+    /// This must be called after the request has been fully parsed, which consumes all of its
+    /// arguments and fds. Only then is the object removed, so a destructor request is always
+    /// parsed against the object it destroys.
+    ///
+    /// Returns `None` if the request is not a destructor or there is no object at `id`
+    pub fn complete(&mut self, id: u32, request: &impl Destructor) -> Option<Object<D>> {
+        match request.is_destructor() {
+            true => self.objects.remove(&id),
+            false => None,
+        }
+    }
+
+    /// Ensures that `id` references a live object that implements the interface `T`
+    pub fn check<T: Interface>(&self, id: ObjectId<T>) -> Result<(), ObjectError> {
+        let object = self
+            .objects
+            .get(&id.value())
+            .ok_or(ObjectError::Missing(id.value()))?;
+
+        if &*object.interface != T::NAME {
+            return Err(ObjectError::Mismatch {
+                id: id.value(),
+                expected: T::NAME,
+                found: object.interface.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Converts a raw parsed object `id` into an [`ObjectId<T>`], if it references a live `T`
+    ///
+    /// This is a validation step for after parsing, rather than a parser of its own.
+    /// Parsers are resumable and may be held across many reads while a message is incomplete,
+    /// so a parser borrowing the map would block every insert and removal in the meantime.
+    /// Validating once the message is fully parsed also ensures the map reflects every
+    /// request that came before it.
+    pub fn checked_id<T: Interface>(&self, id: u32) -> Result<ObjectId<T>, ObjectError> {
+        let id = ObjectId::from_value(id);
+        self.check(id)?;
+        Ok(id)
+    }
+
+    /// Builds a request parser for a message sent to object `id`
+    ///
+    /// The interface recorded for `id` is passed to `parser_for` along with `opcode`.
+    /// This is usually the generated `parser_for` function of a protocol module.
+    ///
+    /// Returns `None` if there is no object at `id` or `parser_for` returns `None`
+    pub fn parser<P>(
+        &self,
+        id: u32,
+        opcode: u16,
+        parser_for: impl FnOnce(&str, u16) -> Option<P>,
+    ) -> Option<P> {
+        let object = self.objects.get(&id)?;
+        parser_for(&object.interface, opcode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, buffer::IterExt, protocols::wayland};
+
+    use super::*;
+
+    #[test]
+    fn parse_bound_object() {
+        let mut map = ObjectMap::new();
+        map.insert_by_name(3, "wl_compositor", 6, ()).unwrap();
+
+        // wl_compositor::create_surface takes a single new_id argument
+        let mut parser = map.parser(3, 0, wayland::parser_for).unwrap();
+        let bytes = 7u32.to_ne_bytes().into_iter().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+
+        let wayland::Request::WlCompositor(
+            wayland::wl_compositor::WlCompositorRequest::CreateSurface(_),
+        ) = request
+        else {
+            panic!("parsed unexpected request: {request:?}");
+        };
+    }
+
+    #[test]
+    fn validate_objects() {
+        let mut map = ObjectMap::new();
+        map.insert_by_name(3, "wl_surface", 6, ()).unwrap();
+        map.insert_by_name(4, "wl_region", 1, ()).unwrap();
+
+        // the new_id is not checked, and the parent must be a wl_surface
+        let mut parser = wayland::parser_for("wl_subcompositor", 1).unwrap();
+        let mut ids = [5u32, 3, 3].map(u32::to_ne_bytes).concat();
+        let request = parser
+            .parse(ids.iter().copied().buffer(), std::iter::empty().buffer())
+            .unwrap();
+        assert_eq!(request.validate_objects(&map), Ok(()));
+
+        // a wl_region is not a valid surface
+        ids[4..8].copy_from_slice(&4u32.to_ne_bytes());
+        let mut parser = wayland::parser_for("wl_subcompositor", 1).unwrap();
+        let request = parser
+            .parse(ids.iter().copied().buffer(), std::iter::empty().buffer())
+            .unwrap();
+        assert_eq!(
+            request.validate_objects(&map),
+            Err(ObjectError::Mismatch {
+                id: 4,
+                expected: "wl_surface",
+                found: "wl_region".into(),
+            })
+        );
+
+        // a nullable object may be zero
+        let bytes = [0u32, 0, 0].map(u32::to_ne_bytes).concat();
+        let mut parser = wayland::parser_for("wl_surface", 1).unwrap();
+        let request = parser
+            .parse(bytes.iter().copied().buffer(), std::iter::empty().buffer())
+            .unwrap();
+        assert_eq!(request.validate_objects(&map), Ok(()));
+    }
+
+    #[test]
+    fn parse_boxed_request() {
+        let mut parser = wayland::request_parser("wl_compositor", 0).unwrap();
+        let bytes = 7u32.to_ne_bytes().into_iter().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        assert!(matches!(request, wayland::Request::WlCompositor(_)));
+
+        assert!(wayland::request_parser("wl_unknown", 0).is_none());
+        assert!(wayland::request_parser("wl_compositor", 100).is_none());
+    }
+
+    #[test]
+    fn complete_destructor() {
+        let mut map = ObjectMap::new();
+        map.insert_by_name(3, "wl_buffer", 1, ()).unwrap();
+
+        // wl_buffer::destroy is a destructor with no arguments
+        let mut parser = map.parser(3, 0, wayland::parser_for).unwrap();
+        let request = parser
+            .parse(std::iter::empty().buffer(), std::iter::empty().buffer())
+            .unwrap();
+        assert!(request.is_destructor());
+
+        let object = map.complete(3, &request).unwrap();
+        assert_eq!(&*object.interface, "wl_buffer");
+        assert!(map.get(3).is_none());
+
+        // wl_compositor::create_surface does not destroy the compositor
+        map.insert_by_name(4, "wl_compositor", 6, ()).unwrap();
+        let mut parser = map.parser(4, 0, wayland::parser_for).unwrap();
+        let bytes = 7u32.to_ne_bytes().into_iter().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        assert!(map.complete(4, &request).is_none());
+        assert!(map.get(4).is_some());
+    }
+
+    #[test]
+    fn checked_id() {
+        let mut map = ObjectMap::new();
+        map.insert_by_name(3, "wl_region", 1, ()).unwrap();
+
+        let id = map.checked_id::<wayland::WlRegion>(3).unwrap();
+        assert_eq!(id.value(), 3);
+
+        assert_eq!(
+            map.checked_id::<wayland::WlSurface>(3).unwrap_err(),
+            ObjectError::Mismatch {
+                id: 3,
+                expected: "wl_surface",
+                found: "wl_region".into(),
+            }
+        );
+        assert_eq!(
+            map.checked_id::<wayland::WlSurface>(4).unwrap_err(),
+            ObjectError::Missing(4)
+        );
+    }
+
+    #[test]
+    fn insert_duplicate() {
+        let mut map = ObjectMap::new();
+        let id = NewId::<wayland::WlSurface>::from_value(3);
+        assert_eq!(map.insert(id, 6, ()), Ok(()));
+        assert_eq!(map.insert(id, 6, ()), Err(ObjectError::AlreadyExists(3)));
+        assert_eq!(
+            map.insert_by_name(3, "wl_region", 1, ()),
+            Err(ObjectError::AlreadyExists(3))
+        );
+
+        // the original object should be left untouched
+        assert_eq!(&*map.get(3).unwrap().interface, "wl_surface");
+
+        // once removed, the id can be reused
+        map.remove(3);
+        assert_eq!(map.insert_by_name(3, "wl_region", 1, ()), Ok(()));
+    }
+
+    #[test]
+    fn insert_by_name_duplicate() {
+        let mut map = ObjectMap::new();
+        assert_eq!(map.insert_by_name(3, "wl_compositor", 6, ()), Ok(()));
+        assert_eq!(
+            map.insert_by_name(3, "wl_compositor", 6, ()),
+            Err(ObjectError::AlreadyExists(3))
+        );
+
+        // the typed insert shares the same check
+        let id = NewId::<wayland::WlSurface>::from_value(3);
+        assert_eq!(map.insert(id, 6, ()), Err(ObjectError::AlreadyExists(3)));
+        assert_eq!(&*map.get(3).unwrap().interface, "wl_compositor");
+    }
+
+    #[test]
+    fn destructor_opcodes() {
+        // wl_surface::destroy is the first request
+        assert_eq!(wayland::wl_surface::DESTRUCTOR_REQUESTS, &[0]);
+        assert_eq!(wayland::destructor_requests("wl_surface"), Some(&[0][..]));
+        assert_eq!(wayland::destructor_events("wl_surface"), Some(&[][..]));
+
+        // wl_callback::done is a destructor event
+        assert_eq!(wayland::destructor_events("wl_callback"), Some(&[0][..]));
+        assert!(wayland::destructor_requests("wl_unknown").is_none());
+    }
+
+    #[test]
+    fn opcode_names() {
+        assert_eq!(wayland::wl_surface::request_name(1), Some("attach"));
+        assert_eq!(wayland::wl_surface::event_name(0), Some("enter"));
+        assert_eq!(wayland::wl_surface::request_name(100), None);
+
+        assert_eq!(wayland::request_name("wl_display", 1), Some("get_registry"));
+        assert_eq!(wayland::event_name("wl_callback", 0), Some("done"));
+        assert_eq!(wayland::request_name("wl_unknown", 0), None);
+    }
+
+    #[test]
+    fn protocol_versions() {
+        assert_eq!(wayland::PROTOCOL_NAME, "wayland");
+        assert_eq!(wayland::INTERFACE_COUNT, 23);
+        assert_eq!(wayland::wl_compositor::VERSION, 6);
+        assert_eq!(
+            <wayland::WlCompositor as Interface>::VERSION,
+            wayland::wl_compositor::VERSION
+        );
+    }
+
+    #[test]
+    fn parse_unknown_object() {
+        let mut map = ObjectMap::new();
+        assert!(map.parser(3, 0, wayland::parser_for).is_none());
+
+        map.insert_by_name(3, "wl_unknown", 1, ()).unwrap();
+        assert!(map.parser(3, 0, wayland::parser_for).is_none());
+    }
+
+    #[test]
+    fn register_new_ids() {
+        use crate::{buffer::SliceBuffer, types::RawString};
+
+        let mut map = ObjectMap::<()>::new();
+
+        // wl_display::sync creates a wl_callback
+        let mut parser = wayland::parser_for("wl_display", 0).unwrap();
+        let bytes = 2u32.to_ne_bytes().into_iter().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        request.register_new_ids(&mut map, 1).unwrap();
+        assert_eq!(&*map.get(2).unwrap().interface, "wl_callback");
+
+        // wl_registry::bind creates an object with its own interface and version
+        let name = RawString::from_bytes(b"wl_compositor"[..].into());
+        let mut body = 1u32.to_ne_bytes().to_vec();
+        body.extend((name.len() as u32 + 1).to_ne_bytes());
+        body.extend(b"wl_compositor\0\0\0");
+        body.extend([5u32, 3].map(u32::to_ne_bytes).concat());
+
+        let mut parser = wayland::parser_for("wl_registry", 0).unwrap();
+        let request = parser
+            .parse(SliceBuffer::new(&body), std::iter::empty().buffer())
+            .unwrap();
+        request.register_new_ids(&mut map, 1).unwrap();
+        let object = map.get(3).unwrap();
+        assert_eq!((&*object.interface, object.version), ("wl_compositor", 5));
+
+        // registering the same ids again should fail
+        assert_eq!(
+            request.register_new_ids(&mut map, 1),
+            Err(ObjectError::AlreadyExists(3))
+        );
+    }
+
+    #[test]
+    fn object_data() {
+        let mut map = ObjectMap::<Vec<u8>>::new();
+        map.insert_by_name(3, "wl_surface", 6, Vec::new()).unwrap();
+
+        map.get_mut(3).unwrap().extend([1, 2, 3]);
+        assert_eq!(map.get(3).unwrap().data, [1, 2, 3]);
+        assert!(map.get_mut(4).is_none());
+
+        // the data is returned with the object when it is destroyed
+        let object = map.remove(3).unwrap();
+        assert_eq!(object.data, [1, 2, 3]);
+    }
+}
@@ -1,25 +1,45 @@
 use std::os::fd::OwnedFd;
 
-use crate::{Buffer, parser::Builder};
+use crate::{Buffer, ParseError, parser::Builder};
 
 use super::{uint, utils};
 
+/// The default maximum length of an array
+///
+/// This matches the largest possible message, as the wire length field is 16 bits.
+pub const DEFAULT_MAX_LEN: u32 = u16::MAX as u32;
+
 pub struct Parser {
     bytes: Option<Builder<utils::VecParser>>,
     len: uint::Parser,
     padding: u32,
+    max_len: u32,
 }
 
 impl Parser {
     pub const fn new() -> Self {
+        Self::with_max_len(DEFAULT_MAX_LEN)
+    }
+
+    /// Returns a new parser that fails on arrays longer than `max_len` bytes
+    ///
+    /// The length is read from the wire, so this prevents a client from forcing a huge allocation.
+    pub const fn with_max_len(max_len: u32) -> Self {
         Self {
             bytes: None,
             len: uint::Parser::new(),
             padding: 0,
+            max_len,
         }
     }
 }
 
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl crate::Parser for Parser {
     type Output = Box<[u8]>;
 
@@ -27,35 +47,91 @@ impl crate::Parser for Parser {
         &mut self,
         mut bytes: impl Buffer<u8>,
         mut fds: impl Buffer<OwnedFd>,
-    ) -> Option<Self::Output> {
+    ) -> Result<Self::Output, ParseError> {
         // try to get any pending bytes builder
         let mut builder = match self.bytes.take() {
             Some(bytes) => bytes,
             None => {
                 // if there was none, build the length and padding
                 let len = self.len.parse(&mut bytes, &mut fds)?;
-                self.padding = ((len + 3) & !3) - len;
+
+                // empty arrays have no bytes or padding, so skip building them
+                if len == 0 {
+                    return Ok(Box::default());
+                }
+
+                // ensure the length is within bounds before allocating
+                if len > self.max_len {
+                    return Err(ParseError::TooLong {
+                        len,
+                        max: self.max_len,
+                    });
+                }
+
+                self.padding = utils::padding(len, 4);
                 Builder::new(utils::VecParser::new(len as usize))
             }
         };
 
         // then try to parse all the array bytes
-        if builder.parse(&mut bytes, &mut fds).is_none() {
+        if let Err(e) = builder.parse(&mut bytes, &mut fds) {
             self.bytes = Some(builder);
-            return None;
+            return Err(e);
         };
 
         // keep parsing bytes until the padding is zero
         while self.padding > 0 {
             if bytes.take().is_none() {
                 self.bytes = Some(builder);
-                return None;
+                return Err(ParseError::Incomplete);
             }
 
             self.padding -= 1;
         }
 
         // then consume and return the bytes
-        Some(builder.finish()?.into_boxed_slice())
+        Ok(builder.finish()?.into_boxed_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser as _, buffer::IterExt};
+
+    use super::*;
+
+    #[test]
+    fn parse_empty() {
+        let bytes = [0u32, 42].map(u32::to_ne_bytes).concat();
+        let mut bytes = bytes.into_iter().buffer();
+
+        // the empty array should not consume any of the following bytes
+        let array = Parser::new()
+            .parse(&mut bytes, std::iter::empty().buffer())
+            .unwrap();
+        assert!(array.is_empty());
+
+        let next = uint::Parser::new()
+            .parse(&mut bytes, std::iter::empty().buffer())
+            .unwrap();
+        assert_eq!(next, 42);
+    }
+
+    #[test]
+    fn reject_long() {
+        let bytes = u32::MAX.to_ne_bytes().into_iter().buffer();
+        let result = Parser::new().parse(bytes, std::iter::empty().buffer());
+        assert_eq!(
+            result,
+            Err(ParseError::TooLong {
+                len: u32::MAX,
+                max: DEFAULT_MAX_LEN,
+            })
+        );
+
+        let bytes = [4u32, 0].map(u32::to_ne_bytes).concat();
+        let result =
+            Parser::with_max_len(3).parse(bytes.into_iter().buffer(), std::iter::empty().buffer());
+        assert_eq!(result, Err(ParseError::TooLong { len: 4, max: 3 }));
     }
 }
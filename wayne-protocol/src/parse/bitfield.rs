@@ -0,0 +1,42 @@
+use std::{marker::PhantomData, os::fd::OwnedFd};
+
+use crate::{
+    Buffer, ParseError,
+    types::{Bitfield, Flags},
+};
+
+use super::uint;
+
+/// Parses a `uint` bitfield into [`Flags`], keeping any bits unknown to `E`
+pub struct Parser<E> {
+    _type: PhantomData<fn() -> E>,
+    bits: uint::Parser,
+}
+
+impl<E> Parser<E> {
+    pub const fn new() -> Self {
+        Self {
+            _type: PhantomData,
+            bits: uint::Parser::new(),
+        }
+    }
+}
+
+impl<E> Default for Parser<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Bitfield> crate::Parser for Parser<E> {
+    type Output = Flags<E>;
+
+    fn parse(
+        &mut self,
+        bytes: impl Buffer<u8>,
+        fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        let bits = self.bits.parse(bytes, fds)?;
+        Ok(Flags::from_bits(bits))
+    }
+}
@@ -0,0 +1,83 @@
+use std::os::fd::OwnedFd;
+
+use crate::{Buffer, ParseError};
+
+/// Runs a parser only when a condition holds, such as a flag bit from a previously parsed argument
+///
+/// This is useful when the presence of an argument depends on an earlier one,
+/// like an fd that is only sent when a flag is set. The condition is captured when the
+/// parser is created, so it should be built once the value it depends on has been parsed.
+///
+/// The output is `None` if the condition did not hold, without consuming any bytes or fds.
+pub struct Parser<P> {
+    parser: Option<P>,
+}
+
+impl<P> Parser<P> {
+    pub fn new(condition: bool, parser: P) -> Self {
+        Self {
+            parser: condition.then_some(parser),
+        }
+    }
+}
+
+impl<P: crate::Parser> crate::Parser for Parser<P> {
+    type Output = Option<P::Output>;
+
+    fn parse(
+        &mut self,
+        bytes: impl Buffer<u8>,
+        fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        match &mut self.parser {
+            Some(parser) => parser.parse(bytes, fds).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use crate::{
+        Parser as _,
+        buffer::{SliceBuffer, VecFdBuffer},
+        parse::{fd, uint},
+    };
+
+    use super::*;
+
+    const HAS_FD: u32 = 1;
+
+    /// Parses a flags argument followed by an fd that is only sent when `HAS_FD` is set
+    fn parse_request(
+        body: &[u8],
+        fds: &mut VecFdBuffer,
+    ) -> Result<(u32, Option<OwnedFd>), ParseError> {
+        let mut bytes = SliceBuffer::new(body);
+        let flags = uint::Parser::new().parse(&mut bytes, &mut *fds)?;
+        let fd =
+            Parser::new(flags & HAS_FD != 0, fd::Parser::new()).parse(&mut bytes, &mut *fds)?;
+        Ok((flags, fd))
+    }
+
+    #[test]
+    fn parse_flagged_fd() {
+        let (a, _b) = UnixStream::pair().unwrap();
+        let mut fds = VecFdBuffer::from(vec![OwnedFd::from(a)]);
+
+        // without the flag the fd should be left in the buffer
+        let (_, fd) = parse_request(&0u32.to_ne_bytes(), &mut fds).unwrap();
+        assert!(fd.is_none());
+        assert_eq!(fds.len(), 1);
+
+        let (_, fd) = parse_request(&HAS_FD.to_ne_bytes(), &mut fds).unwrap();
+        assert!(fd.is_some());
+        assert!(fds.is_empty());
+
+        // with the flag the fd is required
+        let result = parse_request(&HAS_FD.to_ne_bytes(), &mut fds);
+        assert!(result.is_err_and(|e| e.is_incomplete()));
+    }
+}
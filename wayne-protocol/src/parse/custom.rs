@@ -1,6 +1,6 @@
 use std::os::fd::OwnedFd;
 
-use crate::{Buffer, parser::Builder, types::id::CustomNewId};
+use crate::{Buffer, ParseError, parser::Builder, types::id::CustomNewId};
 
 use super::{string, uint};
 
@@ -20,6 +20,12 @@ impl Parser {
     }
 }
 
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl crate::Parser for Parser {
     type Output = CustomNewId;
 
@@ -27,12 +33,12 @@ impl crate::Parser for Parser {
         &mut self,
         mut bytes: impl Buffer<u8>,
         mut fds: impl Buffer<OwnedFd>,
-    ) -> Option<Self::Output> {
+    ) -> Result<Self::Output, ParseError> {
         self.name.parse(&mut bytes, &mut fds)?;
         self.version.parse(&mut bytes, &mut fds)?;
         self.value.parse(&mut bytes, &mut fds)?;
 
-        Some(CustomNewId {
+        Ok(CustomNewId {
             name: self.name.finish()?,
             version: self.version.finish()?,
             value: self.value.finish()?,
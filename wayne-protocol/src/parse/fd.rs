@@ -1,6 +1,6 @@
 use std::os::fd::OwnedFd;
 
-use crate::Buffer;
+use crate::{Buffer, ParseError};
 
 pub struct Parser(());
 
@@ -10,10 +10,20 @@ impl Parser {
     }
 }
 
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl crate::Parser for Parser {
     type Output = OwnedFd;
 
-    fn parse(&mut self, _: impl Buffer<u8>, mut fds: impl Buffer<OwnedFd>) -> Option<Self::Output> {
-        fds.take()
+    fn parse(
+        &mut self,
+        _: impl Buffer<u8>,
+        mut fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        fds.take().ok_or(ParseError::Incomplete)
     }
 }
@@ -1,28 +1,92 @@
-use std::os::fd::OwnedFd;
+use std::{marker::PhantomData, os::fd::OwnedFd};
 
-use fixed::types::I28F4;
-
-use crate::Buffer;
+use crate::{Buffer, ParseError, types::Fixed};
 
 use super::int;
 
-pub struct Parser {
+/// Parses a `fixed` argument into `N`, which is `f32` by default
+///
+/// Using [`Fixed`] as the output keeps the raw bits, so integer coordinates can be read
+/// with [`Fixed::int_part`] without any float conversion.
+pub struct Parser<N = f32> {
+    _type: PhantomData<fn() -> N>,
     bits: int::Parser,
 }
 
-impl Parser {
+impl<N> Parser<N> {
     pub const fn new() -> Self {
         Self {
+            _type: PhantomData,
             bits: int::Parser::new(),
         }
     }
 }
 
-impl crate::Parser for Parser {
-    type Output = f32;
+impl<N> Default for Parser<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: From<Fixed>> crate::Parser for Parser<N> {
+    type Output = N;
 
-    fn parse(&mut self, bytes: impl Buffer<u8>, fds: impl Buffer<OwnedFd>) -> Option<Self::Output> {
+    fn parse(
+        &mut self,
+        bytes: impl Buffer<u8>,
+        fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        // wl_fixed is a signed 24.8 fixed point number
         let bits = self.bits.parse(bytes, fds)?;
-        Some(I28F4::from_bits(bits).to_num())
+        Ok(Fixed::from_bits(bits).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::parse_all;
+
+    use super::*;
+
+    #[test]
+    fn decode_fixed() {
+        let table: &[(u32, f32)] = &[
+            (0x0000_0000, 0.0),
+            (0x0000_0001, 0.00390625),
+            (0x0000_0080, 0.5),
+            (0x0000_0100, 1.0),
+            (0x0000_0180, 1.5),
+            (0xFFFF_FFFF, -0.00390625),
+            (0xFFFF_FF80, -0.5),
+            (0xFFFF_FF00, -1.0),
+            (0xFFFF_FE80, -1.5),
+            (0x0100_0000, 65536.0),
+            (0x7FFF_FF00, 8388607.0),
+            (0x8000_0000, -8388608.0),
+        ];
+
+        for &(bits, expected) in table {
+            let bytes = bits.to_ne_bytes();
+            let value: f32 = parse_all(&mut Parser::new(), &bytes, &mut VecDeque::new()).unwrap();
+            assert_eq!(value, expected, "bits {bits:#010x}");
+
+            // zero has a single representation, so it must never decode as negative zero
+            assert!(value != 0.0 || value.is_sign_positive());
+        }
+    }
+
+    #[test]
+    fn decode_raw_fixed() {
+        let bytes = 0xFFFF_FE80u32.to_ne_bytes();
+        let mut parser = Parser::<Fixed>::new();
+        let value = parse_all(&mut parser, &bytes, &mut VecDeque::new()).unwrap();
+        assert_eq!(value.bits(), -0x180);
+        assert_eq!((value.int_part(), value.frac()), (-2, 0x80));
+
+        let mut parser = Parser::<f64>::new();
+        let value = parse_all(&mut parser, &bytes, &mut VecDeque::new()).unwrap();
+        assert_eq!(value, -1.5);
     }
 }
@@ -1,9 +1,71 @@
 mod utils;
 
 pub mod array;
+pub mod bitfield;
+pub mod cond;
 pub mod custom;
 pub mod fd;
 pub mod float;
 pub mod int;
+pub mod obj_id_array;
+pub mod sized_fd;
+pub mod streaming_array;
 pub mod string;
 pub mod uint;
+
+use std::os::fd::OwnedFd;
+
+use crate::{
+    Parser,
+    types::{Fixed, RawString, id::CustomNewId},
+};
+
+/// A type with a default parser for its wire form
+///
+/// This is used by [`parse_args`](crate::parse_args) to pick a parser for each argument type.
+pub trait Arg: Sized {
+    type Parser: Parser<Output = Self>;
+
+    /// Returns a new parser for this type
+    fn parser() -> Self::Parser;
+}
+
+macro_rules! impl_arg {
+    ($($ty:ty => $module:ident),* $(,)?) => {
+        $(
+            impl Arg for $ty {
+                type Parser = $module::Parser;
+
+                fn parser() -> Self::Parser {
+                    $module::Parser::new()
+                }
+            }
+        )*
+    };
+}
+
+impl_arg! {
+    i32 => int,
+    u32 => uint,
+    f32 => float,
+    RawString => string,
+    Box<[u8]> => array,
+    OwnedFd => fd,
+    CustomNewId => custom,
+}
+
+impl Arg for Option<RawString> {
+    type Parser = string::NullableParser;
+
+    fn parser() -> Self::Parser {
+        string::NullableParser::new()
+    }
+}
+
+impl Arg for Fixed {
+    type Parser = float::Parser<Fixed>;
+
+    fn parser() -> Self::Parser {
+        float::Parser::new()
+    }
+}
@@ -0,0 +1,76 @@
+use std::{marker::PhantomData, os::fd::OwnedFd};
+
+use crate::{Buffer, ParseError, types::id::ObjectId};
+
+use super::array;
+
+/// Parses an array of `u32` object ids into typed [`ObjectId`]s
+///
+/// Fails with [`ParseError::UnalignedArray`] if the array length is not a multiple of 4.
+pub struct Parser<T> {
+    _type: PhantomData<fn() -> T>,
+    array: array::Parser,
+}
+
+impl<T> Parser<T> {
+    pub const fn new() -> Self {
+        Self {
+            _type: PhantomData,
+            array: array::Parser::new(),
+        }
+    }
+}
+
+impl<T> Default for Parser<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> crate::Parser for Parser<T> {
+    type Output = Vec<ObjectId<T>>;
+
+    fn parse(
+        &mut self,
+        bytes: impl Buffer<u8>,
+        fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        let array = self.array.parse(bytes, fds)?;
+        if array.len() % 4 != 0 {
+            return Err(ParseError::UnalignedArray(array.len()));
+        }
+
+        let ids = array.chunks_exact(4).map(|id| {
+            let value = u32::from_ne_bytes(id.try_into().unwrap());
+            ObjectId::from_value(value)
+        });
+
+        Ok(ids.collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser as _, buffer::IterExt};
+
+    use super::*;
+
+    #[test]
+    fn parse_ids() {
+        let bytes = [12u32, 3, 4, 5].map(u32::to_ne_bytes).concat();
+        let ids = Parser::<()>::new()
+            .parse(bytes.into_iter().buffer(), std::iter::empty().buffer())
+            .unwrap();
+        let ids = ids.into_iter().map(ObjectId::value).collect::<Vec<_>>();
+        assert_eq!(ids, [3, 4, 5]);
+    }
+
+    #[test]
+    fn reject_unaligned() {
+        let mut bytes = 6u32.to_ne_bytes().to_vec();
+        bytes.extend([1, 2, 3, 4, 5, 6, 0, 0]);
+        let result =
+            Parser::<()>::new().parse(bytes.into_iter().buffer(), std::iter::empty().buffer());
+        assert_eq!(result, Err(ParseError::UnalignedArray(6)));
+    }
+}
@@ -0,0 +1,90 @@
+use std::os::fd::OwnedFd;
+
+use crate::{Buffer, ParseError, parser::Builder};
+
+use super::{fd, uint};
+
+/// Parses an fd followed by a `uint` size describing it
+///
+/// This is the common pattern for shared memory, such as `wl_shm::create_pool`
+/// and `wl_keyboard::keymap`. The fd is taken from the fd buffer and the size from the bytes.
+pub struct Parser {
+    fd: Builder<fd::Parser>,
+    size: Builder<uint::Parser>,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            fd: Builder::new(fd::Parser::new()),
+            size: Builder::new(uint::Parser::new()),
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl crate::Parser for Parser {
+    type Output = (OwnedFd, u32);
+
+    fn parse(
+        &mut self,
+        mut bytes: impl Buffer<u8>,
+        mut fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        self.fd.parse(&mut bytes, &mut fds)?;
+        self.size.parse(&mut bytes, &mut fds)?;
+
+        Ok((self.fd.finish()?, self.size.finish()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, fs::File, io::Write};
+
+    use crate::{Parser as _, buffer::IterExt, types::MappedFd};
+
+    use super::*;
+
+    #[test]
+    fn parse_and_map() {
+        const DATA: &[u8] = b"wayne keymap";
+
+        let mut file: File = unsafe {
+            let fd = libc::memfd_create(c"wayne-test".as_ptr(), libc::MFD_CLOEXEC);
+            assert!(fd >= 0);
+            std::os::fd::FromRawFd::from_raw_fd(fd)
+        };
+        file.write_all(DATA).unwrap();
+
+        let mut fds = VecDeque::from([OwnedFd::from(file)]);
+        let size = (DATA.len() as u32).to_ne_bytes();
+
+        // feed the size one byte at a time to ensure the fd is held between calls
+        let mut parser = Parser::new();
+        for byte in &size[..3] {
+            let bytes = std::iter::once(*byte).buffer();
+            assert!(
+                parser
+                    .parse(bytes, &mut fds)
+                    .is_err_and(|e| e.is_incomplete())
+            );
+        }
+
+        let bytes = std::iter::once(size[3]).buffer();
+        let (fd, size) = parser.parse(bytes, &mut fds).unwrap();
+        assert_eq!(size as usize, DATA.len());
+
+        // the memfd is only owned by this test, so it can not be truncated while mapped
+        let mapped = MappedFd::map(&fd, size as usize).unwrap();
+        assert_eq!(unsafe { mapped.as_bytes() }, DATA);
+
+        // a size larger than the file is rejected instead of faulting when read
+        assert!(MappedFd::map(&fd, DATA.len() + 1).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::os::fd::OwnedFd;
+
+use crate::{Buffer, ParseError};
+
+use super::{uint, utils};
+
+/// The largest chunk of array bytes passed to the callback at once
+pub const CHUNK_LEN: usize = 256;
+
+/// Parses an array by passing its bytes to a callback as they arrive, instead of buffering them
+///
+/// The callback is called with each chunk of bytes that are available, in order, across as many
+/// partial parses as it takes for the whole array to arrive. Nothing is allocated, so unlike the
+/// buffered [`array`](super::array) parser there is no need for a maximum length.
+///
+/// The output is the total length of the array in bytes.
+pub struct Parser<F: FnMut(&[u8])> {
+    callback: F,
+    len: uint::Parser,
+    total: u32,
+    remaining: Option<u32>,
+    padding: u32,
+}
+
+impl<F: FnMut(&[u8])> Parser<F> {
+    pub const fn new(callback: F) -> Self {
+        Self {
+            callback,
+            len: uint::Parser::new(),
+            total: 0,
+            remaining: None,
+            padding: 0,
+        }
+    }
+
+    /// Consumes the parser and returns the callback
+    pub fn into_callback(self) -> F {
+        self.callback
+    }
+}
+
+impl<F: FnMut(&[u8])> crate::Parser for Parser<F> {
+    type Output = u32;
+
+    fn parse(
+        &mut self,
+        mut bytes: impl Buffer<u8>,
+        mut fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        let mut remaining = match self.remaining {
+            Some(remaining) => remaining,
+            None => {
+                let len = self.len.parse(&mut bytes, &mut fds)?;
+                self.total = len;
+                self.padding = utils::padding(len, 4);
+                len
+            }
+        };
+
+        // pass the bytes to the callback in chunks as they become available
+        let mut chunk = [0u8; CHUNK_LEN];
+        while remaining > 0 {
+            let mut chunk_len = 0;
+            while chunk_len < CHUNK_LEN && chunk_len < remaining as usize {
+                let Some(byte) = bytes.take() else {
+                    break;
+                };
+
+                chunk[chunk_len] = byte;
+                chunk_len += 1;
+            }
+
+            if chunk_len > 0 {
+                (self.callback)(&chunk[..chunk_len]);
+                remaining -= chunk_len as u32;
+            }
+
+            // a short chunk means the buffer ran out before the array was complete
+            if remaining > 0 && chunk_len < CHUNK_LEN {
+                self.remaining = Some(remaining);
+                return Err(ParseError::Incomplete);
+            }
+        }
+
+        // mark the array bytes as complete while waiting for the padding
+        self.remaining = Some(0);
+        while self.padding > 0 {
+            bytes.take().ok_or(ParseError::Incomplete)?;
+            self.padding -= 1;
+        }
+
+        Ok(self.total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser as _, buffer::IterExt};
+
+    use super::*;
+
+    #[test]
+    fn stream_partial_reads() {
+        let data = (0..601u32).map(|i| i as u8).collect::<Vec<_>>();
+        let mut bytes = 601u32.to_ne_bytes().to_vec();
+        bytes.extend(&data);
+
+        let mut received = Vec::new();
+        let mut chunks = 0;
+        let mut parser = Parser::new(|chunk: &[u8]| {
+            assert!(chunk.len() <= CHUNK_LEN);
+            received.extend_from_slice(chunk);
+            chunks += 1;
+        });
+
+        // feed the array in uneven pieces, then the trailing padding is missing
+        let mut start = 0;
+        for end in [2, 100, 500, bytes.len()] {
+            let piece = bytes[start..end].iter().copied().buffer();
+            let result = parser.parse(piece, std::iter::empty().buffer());
+            assert_eq!(result, Err(ParseError::Incomplete));
+            start = end;
+        }
+
+        let result = parser.parse(
+            0u32.to_ne_bytes().into_iter().buffer(),
+            std::iter::empty().buffer(),
+        );
+        assert_eq!(result, Ok(601));
+
+        assert_eq!(received, data);
+        assert!(chunks >= 3);
+    }
+}
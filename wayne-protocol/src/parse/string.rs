@@ -1,9 +1,14 @@
 use std::os::fd::OwnedFd;
 
-use crate::{Buffer, types::RawString};
+use crate::{Buffer, ParseError, types::RawString};
 
 use super::array;
 
+/// Parses a length prefixed, null terminated wayland string
+///
+/// The null terminator is counted in the length and is stripped from the output.
+/// A length of zero is a null string, which is rejected. Use [`NullableParser`] for
+/// arguments that allow null.
 pub struct Parser {
     array: array::Parser,
 }
@@ -14,13 +19,172 @@ impl Parser {
             array: array::Parser::new(),
         }
     }
+
+    /// Returns a new parser that fails on strings longer than `max_len` bytes
+    ///
+    /// The length includes the null terminator.
+    pub const fn with_max_len(max_len: u32) -> Self {
+        Self {
+            array: array::Parser::with_max_len(max_len),
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl crate::Parser for Parser {
     type Output = RawString;
 
-    fn parse(&mut self, bytes: impl Buffer<u8>, fds: impl Buffer<OwnedFd>) -> Option<Self::Output> {
-        let array = self.array.parse(bytes, fds)?;
-        Some(RawString::from_bytes(array))
+    fn parse(
+        &mut self,
+        bytes: impl Buffer<u8>,
+        fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        parse_nullable(&mut self.array, bytes, fds)?.ok_or(ParseError::NullString)
+    }
+}
+
+/// Parses a string that may be null, such as an argument marked with `allow-null`
+///
+/// A null string is parsed as `None`, which is distinct from an empty string.
+pub struct NullableParser {
+    array: array::Parser,
+}
+
+impl NullableParser {
+    pub const fn new() -> Self {
+        Self {
+            array: array::Parser::new(),
+        }
+    }
+}
+
+impl Default for NullableParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl crate::Parser for NullableParser {
+    type Output = Option<RawString>;
+
+    fn parse(
+        &mut self,
+        bytes: impl Buffer<u8>,
+        fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        parse_nullable(&mut self.array, bytes, fds)
+    }
+}
+
+fn parse_nullable(
+    array: &mut array::Parser,
+    bytes: impl Buffer<u8>,
+    fds: impl Buffer<OwnedFd>,
+) -> Result<Option<RawString>, ParseError> {
+    let mut bytes = crate::Parser::parse(array, bytes, fds)?.into_vec();
+
+    // a zero length string is a null string and has no terminator
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    // strip exactly one null terminator
+    if bytes.pop() != Some(0) {
+        return Err(ParseError::MissingNul);
+    }
+
+    // any remaining null bytes are a protocol violation
+    if bytes.contains(&0) {
+        return Err(ParseError::InteriorNul);
+    }
+
+    Ok(Some(RawString::from_bytes(bytes.into_boxed_slice())))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser as _, buffer::IterExt};
+
+    use super::*;
+
+    fn string_bytes(value: &[u8]) -> Vec<u8> {
+        let mut bytes = (value.len() as u32).to_ne_bytes().to_vec();
+        bytes.extend_from_slice(value);
+        bytes.resize((bytes.len() + 3) & !3, 0);
+        bytes
+    }
+
+    #[test]
+    fn strip_terminator() {
+        let bytes = string_bytes(b"abc\0");
+        let string = Parser::new()
+            .parse(bytes.into_iter().buffer(), std::iter::empty().buffer())
+            .unwrap();
+        assert_eq!(string.to_string(), "abc");
+    }
+
+    #[test]
+    fn reject_interior_nul() {
+        let bytes = string_bytes(b"a\0c\0");
+        let result = Parser::new().parse(bytes.into_iter().buffer(), std::iter::empty().buffer());
+        assert_eq!(result, Err(ParseError::InteriorNul));
+
+        let bytes = string_bytes(b"abc");
+        let result = Parser::new().parse(bytes.into_iter().buffer(), std::iter::empty().buffer());
+        assert_eq!(result, Err(ParseError::MissingNul));
+    }
+
+    #[test]
+    fn null_string() {
+        let bytes = 0u32.to_ne_bytes();
+        let result = Parser::new().parse(bytes.into_iter().buffer(), std::iter::empty().buffer());
+        assert_eq!(result, Err(ParseError::NullString));
+
+        let result =
+            NullableParser::new().parse(bytes.into_iter().buffer(), std::iter::empty().buffer());
+        assert_eq!(result, Ok(None));
+        assert_eq!(RawString::nullable_wire_len(None), 4);
+
+        // an empty string is not null, and takes a terminator and padding on the wire
+        let bytes = string_bytes(b"\0");
+        let result =
+            NullableParser::new().parse(bytes.into_iter().buffer(), std::iter::empty().buffer());
+        let string = result.unwrap().unwrap();
+        assert!(string.is_empty());
+        assert_eq!(RawString::nullable_wire_len(Some(&string)), 8);
+    }
+
+    #[test]
+    fn wire_round_trip() {
+        for value in ["", "abc", "wl_compositor"] {
+            let string = RawString::from(value);
+            let bytes = string.to_wire();
+            assert_eq!(bytes.len(), string.wire_len());
+            assert_eq!(bytes, string_bytes(format!("{value}\0").as_bytes()));
+
+            let parsed = Parser::new()
+                .parse(bytes.into_iter().buffer(), std::iter::empty().buffer())
+                .unwrap();
+            assert_eq!(parsed, string);
+        }
+
+        // a null string is only its zero length, while an empty string has a terminator
+        let empty = RawString::from("");
+        for string in [None, Some(&empty)] {
+            let bytes = RawString::nullable_to_wire(string);
+            assert_eq!(bytes.len(), RawString::nullable_wire_len(string));
+
+            let parsed = NullableParser::new()
+                .parse(bytes.into_iter().buffer(), std::iter::empty().buffer())
+                .unwrap();
+            assert_eq!(parsed.as_ref(), string);
+        }
+        assert_eq!(RawString::nullable_to_wire(None), [0; 4]);
+        assert_eq!(empty.to_wire(), string_bytes(b"\0"));
     }
 }
@@ -1,6 +1,6 @@
 use std::os::fd::OwnedFd;
 
-use crate::Buffer;
+use crate::{Buffer, ParseError};
 
 use super::utils;
 
@@ -16,11 +16,21 @@ impl Parser {
     }
 }
 
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl crate::Parser for Parser {
     type Output = u32;
 
-    fn parse(&mut self, bytes: impl Buffer<u8>, fds: impl Buffer<OwnedFd>) -> Option<Self::Output> {
+    fn parse(
+        &mut self,
+        bytes: impl Buffer<u8>,
+        fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
         let bytes = self.bytes.parse(bytes, fds)?;
-        Some(u32::from_ne_bytes(bytes))
+        Ok(u32::from_ne_bytes(bytes))
     }
 }
@@ -1,6 +1,14 @@
 use std::{mem::MaybeUninit, os::fd::OwnedFd};
 
-use crate::Buffer;
+use crate::{Buffer, ParseError};
+
+/// Returns the number of bytes needed to pad `len` up to a multiple of `align`
+///
+/// This works for any non-zero `align`, not just powers of two, and cannot overflow.
+pub const fn padding(len: u32, align: u32) -> u32 {
+    debug_assert!(align > 0, "padding alignment must be non-zero");
+    (align - len % align) % align
+}
 
 pub struct VecParser {
     vec: Option<Vec<u8>>,
@@ -20,7 +28,7 @@ impl crate::Parser for VecParser {
         &mut self,
         mut bytes: impl Buffer<u8>,
         _: impl Buffer<OwnedFd>,
-    ) -> Option<Self::Output> {
+    ) -> Result<Self::Output, ParseError> {
         let mut vec = self
             .vec
             .take()
@@ -29,13 +37,13 @@ impl crate::Parser for VecParser {
         while vec.len() < self.len {
             let Some(byte) = bytes.take() else {
                 self.vec = Some(vec);
-                return None;
+                return Err(ParseError::Incomplete);
             };
 
             vec.push(byte);
         }
 
-        Some(vec)
+        Ok(vec)
     }
 }
 
@@ -60,13 +68,38 @@ impl<const LEN: usize> crate::Parser for ArrayParser<LEN> {
         &mut self,
         mut bytes: impl Buffer<u8>,
         _: impl Buffer<OwnedFd>,
-    ) -> Option<Self::Output> {
+    ) -> Result<Self::Output, ParseError> {
         while self.index < LEN {
-            let byte = bytes.take()?;
+            let byte = bytes.take().ok_or(ParseError::Incomplete)?;
             self.bytes[self.index].write(byte);
             self.index += 1;
         }
 
-        Some(unsafe { core::ptr::read(self.bytes.as_ptr().cast()) })
+        Ok(unsafe { core::ptr::read(self.bytes.as_ptr().cast()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding_alignments() {
+        // an alignment of one never needs padding
+        for len in 0..8 {
+            assert_eq!(padding(len, 1), 0);
+        }
+
+        assert_eq!([0, 1, 2, 3].map(|len| padding(len, 2)), [0, 1, 0, 1]);
+        assert_eq!(
+            [0, 1, 2, 3, 4, 5].map(|len| padding(len, 4)),
+            [0, 3, 2, 1, 0, 3]
+        );
+
+        // alignments do not have to be a power of two
+        assert_eq!([0, 1, 2, 3, 4].map(|len| padding(len, 3)), [0, 2, 1, 0, 2]);
+
+        // lengths near the max should not overflow
+        assert_eq!(padding(u32::MAX, 4), 1);
     }
 }
@@ -1,10 +1,147 @@
 use std::os::fd::OwnedFd;
 
-use crate::Buffer;
+use thiserror::Error;
+
+use crate::{Buffer, buffer::SliceBuffer};
+
+/// An error produced when a [`Parser`] cannot produce its output
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The buffers ran out before parsing finished.
+    ///
+    /// The parser holds its progress and may be resumed once more data is available.
+    #[error("Not enough data to finish parsing")]
+    Incomplete,
+    #[error("String contains an interior null byte")]
+    InteriorNul,
+    #[error("String is missing its null terminator")]
+    MissingNul,
+    #[error("String is null, but the argument does not allow null")]
+    NullString,
+    #[error("Length {len} is longer than the maximum of {max}")]
+    TooLong { len: u32, max: u32 },
+    /// The opcode is out of range for the interface.
+    ///
+    /// This should be reported to the client with a `wl_display::error` of `invalid_method`.
+    #[error("Opcode {opcode} is not a request of {interface}")]
+    UnknownOpcode {
+        interface: &'static str,
+        opcode: u16,
+    },
+    #[error("Message has {_0} bytes left over after parsing")]
+    TrailingBytes(usize),
+    #[error("Array length {_0} is not a multiple of 4")]
+    UnalignedArray(usize),
+}
+
+impl ParseError {
+    /// Returns `true` if the parser can be resumed with more data
+    pub const fn is_incomplete(&self) -> bool {
+        matches!(self, Self::Incomplete)
+    }
+}
 
 pub trait Parser {
     type Output;
-    fn parse(&mut self, bytes: impl Buffer<u8>, fds: impl Buffer<OwnedFd>) -> Option<Self::Output>;
+    fn parse(
+        &mut self,
+        bytes: impl Buffer<u8>,
+        fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError>;
+}
+
+/// An object safe version of [`Parser`]
+///
+/// This is implemented for every [`Parser`], and allows parsers with different types
+/// but the same output to be stored together behind a [`BoxedParser`].
+pub trait DynParser {
+    type Output;
+    fn parse_dyn(
+        &mut self,
+        bytes: &mut dyn Buffer<u8>,
+        fds: &mut dyn Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError>;
+}
+
+impl<P: Parser> DynParser for P {
+    type Output = P::Output;
+
+    fn parse_dyn(
+        &mut self,
+        bytes: &mut dyn Buffer<u8>,
+        fds: &mut dyn Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        self.parse(bytes, fds)
+    }
+}
+
+/// A boxed parser that produces `T`
+pub type BoxedParser<'a, T> = Box<dyn DynParser<Output = T> + 'a>;
+
+impl<T> Parser for BoxedParser<'_, T> {
+    type Output = T;
+
+    fn parse(
+        &mut self,
+        mut bytes: impl Buffer<u8>,
+        mut fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
+        self.as_mut().parse_dyn(&mut bytes, &mut fds)
+    }
+}
+
+/// Parses a complete output from the message `body` using `parser`
+///
+/// Unlike [`Parser::parse`], the body is expected to hold the whole message.
+/// A body that is too short returns [`ParseError::Incomplete`], and a body with bytes
+/// left over after parsing returns [`ParseError::TrailingBytes`].
+pub fn parse_all<P: Parser>(
+    parser: &mut P,
+    body: &[u8],
+    fds: impl Buffer<OwnedFd>,
+) -> Result<P::Output, ParseError> {
+    let mut bytes = SliceBuffer::new(body);
+    let output = parser.parse(&mut bytes, fds)?;
+
+    match bytes.remaining().len() {
+        0 => Ok(output),
+        len => Err(ParseError::TrailingBytes(len)),
+    }
+}
+
+/// Reports a request that was sent with a different number of fds than its type declares
+///
+/// `available` is the number of fds that were sent with the message, and `expected` is the
+/// number the request takes. Fds that are not taken stay queued for the next message that takes fds,
+/// which desynchronizes the fd queue for every following message. So a mismatch is logged as an error
+/// and fails a debug assertion where it happens, rather than surfacing several messages later.
+pub fn check_fd_count(interface: &str, opcode: u16, expected: usize, available: usize) {
+    if expected == available {
+        return;
+    }
+
+    log::error!(
+        "Request {opcode} of {interface} was sent with {available} fds but takes {expected}, the fd queue is now out of sync"
+    );
+    debug_assert_eq!(
+        expected, available,
+        "request {opcode} of {interface} was sent with the wrong number of fds"
+    );
+}
+
+/// Parses the leading output from the message `body` using `parser`, and returns the rest of the body
+///
+/// Unlike [`parse_all`], bytes left over after parsing are not an error.
+/// They are returned as raw bytes alongside the output, which is useful for proxies that only
+/// need to inspect the leading arguments, or extensions with trailing data that is not modeled.
+pub fn parse_with_rest<P: Parser>(
+    parser: &mut P,
+    body: &[u8],
+    fds: impl Buffer<OwnedFd>,
+) -> Result<(P::Output, Box<[u8]>), ParseError> {
+    let mut bytes = SliceBuffer::new(body);
+    let output = parser.parse(&mut bytes, fds)?;
+    Ok((output, bytes.remaining().into()))
 }
 
 pub struct Builder<P: Parser> {
@@ -20,21 +157,268 @@ impl<P: Parser> Builder<P> {
         }
     }
 
-    pub const fn finish(&mut self) -> Option<P::Output> {
-        self.output.take()
+    pub fn finish(&mut self) -> Result<P::Output, ParseError> {
+        self.output.take().ok_or(ParseError::Incomplete)
     }
 }
 
 impl<P: Parser> Parser for Builder<P> {
     type Output = ();
 
-    fn parse(&mut self, bytes: impl Buffer<u8>, fds: impl Buffer<OwnedFd>) -> Option<Self::Output> {
+    fn parse(
+        &mut self,
+        bytes: impl Buffer<u8>,
+        fds: impl Buffer<OwnedFd>,
+    ) -> Result<Self::Output, ParseError> {
         if self.output.is_some() {
-            return Some(());
+            return Ok(());
         }
 
         let output = self.parser.parse(bytes, fds)?;
         self.output = Some(output);
-        Some(())
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{buffer::IterExt, parse::uint, protocols::wayland};
+
+    use super::*;
+
+    /// Parses `body` once in full, then again a single byte at a time,
+    /// ensuring that the resumed parser produces the same output
+    fn parse_resumed(interface: &str, opcode: u16, body: &[u8]) {
+        let mut parser = wayland::parser_for(interface, opcode).unwrap();
+        let bytes = body.iter().copied().buffer();
+        let expected = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+
+        let mut parser = wayland::parser_for(interface, opcode).unwrap();
+        let (last, body) = body.split_last().unwrap();
+        for byte in body {
+            let bytes = std::iter::once(*byte).buffer();
+            let result = parser.parse(bytes, std::iter::empty().buffer());
+            assert_eq!(result.unwrap_err(), ParseError::Incomplete);
+        }
+
+        let bytes = std::iter::once(*last).buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        assert_eq!(format!("{request:?}"), format!("{expected:?}"));
+    }
+
+    #[test]
+    fn resume_many_args() {
+        // wl_shm_pool::create_buffer has 6 fixed size arguments
+        let body = [7u32, 64, 32, 16, 128, 1].map(u32::to_ne_bytes).concat();
+        parse_resumed("wl_shm_pool", 0, &body);
+    }
+
+    #[test]
+    fn resume_padded_args() {
+        // wl_registry::bind has a padded interface string inside its untyped new_id
+        let mut body = 3u32.to_ne_bytes().to_vec();
+        body.extend(11u32.to_ne_bytes());
+        body.extend(b"wl_surface\0\0");
+        body.extend([4u32, 9].map(u32::to_ne_bytes).concat());
+        parse_resumed("wl_registry", 0, &body);
+    }
+
+    #[test]
+    fn request_wire_len() {
+        let mut body = 3u32.to_ne_bytes().to_vec();
+        body.extend(11u32.to_ne_bytes());
+        body.extend(b"wl_surface\0\0");
+        body.extend([4u32, 9].map(u32::to_ne_bytes).concat());
+
+        let mut parser = wayland::parser_for("wl_registry", 0).unwrap();
+        let bytes = body.iter().copied().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        let wayland::Request::WlRegistry(wayland::wl_registry::WlRegistryRequest::Bind(bind)) =
+            request
+        else {
+            panic!("parsed unexpected request: {request:?}");
+        };
+
+        assert_eq!(bind.wire_len(), 8 + body.len());
+    }
+
+    #[test]
+    fn nullable_string_wire_len() {
+        // wl_data_offer::accept allows a null mime type, which is only the zero length on the wire
+        let body = [5u32, 0].map(u32::to_ne_bytes).concat();
+        let mut parser = wayland::parser_for("wl_data_offer", 0).unwrap();
+        let bytes = body.iter().copied().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        let wayland::Request::WlDataOffer(wayland::wl_data_offer::WlDataOfferRequest::Accept(
+            accept,
+        )) = request
+        else {
+            panic!("parsed unexpected request: {request:?}");
+        };
+        assert!(format!("{accept:?}").contains("mime_type: None"));
+        assert_eq!(accept.wire_len(), 8 + body.len());
+
+        // an empty mime type is distinct from a null one
+        let body = [5u32, 1, 0].map(u32::to_ne_bytes).concat();
+        let mut parser = wayland::parser_for("wl_data_offer", 0).unwrap();
+        let bytes = body.iter().copied().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        assert!(format!("{request:?}").contains("mime_type: Some"));
+    }
+
+    #[test]
+    fn clone_requests() {
+        use std::collections::VecDeque;
+
+        use wayland::{wl_registry::WlRegistryRequest, wl_shm::WlShmRequest};
+
+        let mut body = 3u32.to_ne_bytes().to_vec();
+        body.extend(11u32.to_ne_bytes());
+        body.extend(b"wl_surface\0\0");
+        body.extend([4u32, 9].map(u32::to_ne_bytes).concat());
+
+        // requests without fds can be cloned directly
+        let mut parser = wayland::parser_for("wl_registry", 0).unwrap();
+        let bytes = body.iter().copied().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        assert_eq!(request.fd_count(), 0);
+        let wayland::Request::WlRegistry(WlRegistryRequest::Bind(bind)) = request else {
+            panic!("parsed unexpected request: {request:?}");
+        };
+        assert_eq!(format!("{:?}", bind.clone()), format!("{bind:?}"));
+
+        // requests with fds must dup them to be cloned
+        let (fd, _) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut fds = VecDeque::from([OwnedFd::from(fd)]);
+        let body = [5u32, 4096].map(u32::to_ne_bytes).concat();
+        let mut parser = wayland::parser_for("wl_shm", 0).unwrap();
+        let request = parser
+            .parse(body.iter().copied().buffer(), &mut fds)
+            .unwrap();
+        assert_eq!(request.fd_count(), 1);
+        let wayland::Request::WlShm(WlShmRequest::CreatePool(create_pool)) = request else {
+            panic!("parsed unexpected request: {request:?}");
+        };
+        let cloned = create_pool.try_clone().unwrap();
+        assert_eq!(cloned.wire_len(), create_pool.wire_len());
+        assert_eq!(wayland::wl_shm::CreatePoolRequest::FD_COUNT, 1);
+    }
+
+    #[test]
+    fn parse_complete_body() {
+        use wayland::wl_compositor::WlCompositorRequest;
+
+        let mut fds = std::iter::empty().buffer();
+        let body = 7u32.to_ne_bytes();
+        let request = WlCompositorRequest::parse(0, &body, &mut fds).unwrap();
+        assert!(matches!(request, WlCompositorRequest::CreateSurface(_)));
+
+        let result = WlCompositorRequest::parse(9, &body, &mut fds);
+        assert_eq!(
+            result.unwrap_err(),
+            ParseError::UnknownOpcode {
+                interface: "wl_compositor",
+                opcode: 9,
+            }
+        );
+
+        let result = WlCompositorRequest::parse(0, &body[..2], &mut fds);
+        assert_eq!(result.unwrap_err(), ParseError::Incomplete);
+
+        let body = [7u32, 8].map(u32::to_ne_bytes).concat();
+        let result = WlCompositorRequest::parse(0, &body, &mut fds);
+        assert_eq!(result.unwrap_err(), ParseError::TrailingBytes(4));
+    }
+
+    #[test]
+    fn parse_generic_request() {
+        use crate::{RequestParser, parse_request};
+        use wayland::{WlCompositor, wl_compositor::WlCompositorRequest};
+
+        // generic code only needs the interface type to parse its requests
+        fn parse_opcode_zero<I: RequestParser>(body: &[u8]) -> Result<I::Request, ParseError> {
+            parse_request::<I>(0, body, &mut std::iter::empty().buffer())
+        }
+
+        let body = 7u32.to_ne_bytes();
+        let request = parse_opcode_zero::<WlCompositor>(&body).unwrap();
+        assert!(matches!(request, WlCompositorRequest::CreateSurface(_)));
+
+        let result = parse_opcode_zero::<WlCompositor>(&[]);
+        assert_eq!(result.unwrap_err(), ParseError::Incomplete);
+    }
+
+    #[test]
+    fn parse_checked_request() {
+        use crate::interface::parse_request_checked;
+        use wayland::{WlCompositor, wl_compositor::REQUEST_COUNT};
+
+        let mut fds = std::iter::empty().buffer();
+        let body = 7u32.to_ne_bytes();
+        assert!(parse_request_checked::<WlCompositor>(0, &body, &mut fds).is_ok());
+
+        let result = parse_request_checked::<WlCompositor>(REQUEST_COUNT, &body, &mut fds);
+        let error = result.unwrap_err();
+        assert_eq!(
+            error,
+            ParseError::UnknownOpcode {
+                interface: "wl_compositor",
+                opcode: REQUEST_COUNT,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Opcode 2 is not a request of wl_compositor"
+        );
+    }
+
+    #[test]
+    fn parse_checks_fd_count() {
+        use std::os::unix::net::UnixStream;
+
+        use crate::buffer::VecFdBuffer;
+        use wayland::wl_shm::WlShmRequest;
+
+        let (fd, _) = UnixStream::pair().unwrap();
+        let mut fds = VecFdBuffer::from(vec![OwnedFd::from(fd)]);
+        let body = [7u32, 4096].map(u32::to_ne_bytes).concat();
+        let request = WlShmRequest::parse(0, &body, &mut fds).unwrap();
+        assert_eq!(request.fd_count(), 1);
+        assert!(fds.is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic = "was sent with the wrong number of fds"]
+    fn fd_count_mismatch() {
+        use std::os::unix::net::UnixStream;
+
+        use crate::buffer::VecFdBuffer;
+        use wayland::wl_compositor::WlCompositorRequest;
+
+        // wl_compositor::create_surface takes no fds, so the attached fd would be left for a later request
+        let (fd, _) = UnixStream::pair().unwrap();
+        let mut fds = VecFdBuffer::from(vec![OwnedFd::from(fd)]);
+        let body = 7u32.to_ne_bytes();
+        let _ = WlCompositorRequest::parse(0, &body, &mut fds);
+    }
+
+    #[test]
+    fn parse_rest_of_body() {
+        let body = [7u32, 8, 9].map(u32::to_ne_bytes).concat();
+        let mut parser = uint::Parser::new();
+        let (id, rest) = parse_with_rest(&mut parser, &body, std::iter::empty().buffer()).unwrap();
+        assert_eq!(id, 7);
+        assert_eq!(&*rest, &body[4..]);
+
+        // a fully parsed body leaves nothing behind
+        let mut parser = uint::Parser::new();
+        let (_, rest) =
+            parse_with_rest(&mut parser, &body[..4], std::iter::empty().buffer()).unwrap();
+        assert!(rest.is_empty());
+
+        let mut parser = uint::Parser::new();
+        let result = parse_with_rest(&mut parser, &body[..2], std::iter::empty().buffer());
+        assert_eq!(result.unwrap_err(), ParseError::Incomplete);
     }
 }
@@ -1 +1,110 @@
 crate::generate!("protocol/wayland.xml");
+
+use wayland::wl_display::WlDisplayParser;
+
+/// The object id reserved for the `wl_display` singleton
+///
+/// Every connection starts with this object already live, so it is used to bootstrap the
+/// connection with `sync` and `get_registry` before any other object exists.
+/// Clients never allocate it themselves, so it does not need an entry in an [`ObjectMap`](crate::ObjectMap).
+pub const DISPLAY_ID: u32 = 1;
+
+/// Returns a parser for a request with `opcode` sent to object `id`, if `id` is the `wl_display`
+///
+/// Returns `None` if `id` is not [`DISPLAY_ID`] or the opcode is invalid
+pub fn display_parser(id: u32, opcode: u16) -> Option<WlDisplayParser> {
+    match id {
+        DISPLAY_ID => WlDisplayParser::new(opcode),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Interface, Parser, buffer::IterExt, types::RawString};
+
+    use super::*;
+
+    #[test]
+    fn parse_display_request() {
+        use wayland::wl_display::WlDisplayRequest;
+
+        // wl_display::get_registry takes a single new_id argument
+        let mut parser = display_parser(DISPLAY_ID, 1).unwrap();
+        let bytes = 2u32.to_ne_bytes().into_iter().buffer();
+        let request = parser.parse(bytes, std::iter::empty().buffer()).unwrap();
+        assert!(matches!(request, WlDisplayRequest::GetRegistry(_)));
+
+        assert!(display_parser(2, 1).is_none());
+        assert!(display_parser(DISPLAY_ID, 100).is_none());
+    }
+
+    #[test]
+    fn construct_event() {
+        use crate::types::RawEnum;
+        use wayland::wl_output::{GeometryEvent, Subpixel, Transform};
+
+        let make = RawString::from_bytes(b"wayne"[..].into());
+        let model = RawString::from_bytes(b"display"[..].into());
+        let event = GeometryEvent::new(
+            10,
+            20,
+            300,
+            200,
+            RawEnum::from_i32(Subpixel::HorizontalRgb as i32),
+            make,
+            model,
+            RawEnum::from_i32(Transform::Normal as i32),
+        );
+
+        // header, 4 ints, 2 enums and 2 strings each padded to 8 bytes with their length
+        assert_eq!(event.wire_len(), 8 + 4 * 4 + 2 * 4 + 2 * 12);
+    }
+
+    #[test]
+    fn interface_constants() {
+        use crate::types::id::{NewId, ObjectId};
+        use wayland::WlSurface;
+
+        assert_eq!(WlSurface::INTERFACE, "wl_surface");
+        assert_eq!(WlSurface::VERSION, wayland::wl_surface::VERSION);
+        assert_eq!(<WlSurface as Interface>::NAME, WlSurface::INTERFACE);
+
+        // typed ids can recover the wire name of their interface
+        assert_eq!(
+            ObjectId::<WlSurface>::from_value(3).interface(),
+            "wl_surface"
+        );
+        assert_eq!(NewId::<WlSurface>::from_value(3).interface(), "wl_surface");
+    }
+
+    #[test]
+    fn registry_global_names() {
+        use crate::types::id::GlobalName;
+        use wayland::wl_registry::{GlobalEvent, GlobalRemoveEvent, WlRegistryRequest};
+
+        // events take global names rather than plain integers
+        let global = GlobalEvent::new(GlobalName(7), "wl_compositor".into(), 5);
+        let remove = GlobalRemoveEvent::new(GlobalName(7));
+        assert_eq!(global.wire_len(), 8 + 4 + 20 + 4);
+        assert_eq!(remove.wire_len(), 8 + 4);
+
+        // and the name passed back to bind is parsed as one
+        let mut body = 7u32.to_ne_bytes().to_vec();
+        body.extend(RawString::from("wl_compositor").to_wire());
+        body.extend([5u32, 3].map(u32::to_ne_bytes).concat());
+        let request = WlRegistryRequest::parse(0, &body, &mut std::iter::empty().buffer()).unwrap();
+        assert!(format!("{request:?}").contains("name: GlobalName(7)"));
+    }
+
+    #[test]
+    fn enum_entry_since() {
+        use wayland::wl_pointer::AxisSource;
+
+        assert_eq!(AxisSource::Wheel.since(), 1);
+        assert_eq!(AxisSource::WheelTilt.since(), 6);
+        assert_eq!(AxisSource::entry_since(3), Some(6));
+        assert_eq!(AxisSource::entry_since(0), Some(1));
+        assert_eq!(AxisSource::entry_since(4), None);
+    }
+}
@@ -0,0 +1,209 @@
+use thiserror::Error;
+
+use crate::{
+    ObjectMap,
+    object::ObjectError,
+    protocols::wayland::wl_registry::GlobalEvent,
+    types::id::{CustomNewId, GlobalName},
+};
+
+/// An error produced when a client binds a global incorrectly
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    #[error("Global {_0} does not exist")]
+    UnknownGlobal(GlobalName),
+    #[error("Global {name} is a '{expected}' but was bound as a '{found}'")]
+    InterfaceMismatch {
+        name: GlobalName,
+        expected: Box<str>,
+        found: Box<str>,
+    },
+    #[error("Global {name} was bound at version {requested} but only supports up to {max}")]
+    UnsupportedVersion {
+        name: GlobalName,
+        requested: u32,
+        max: u32,
+    },
+    #[error(transparent)]
+    Object(#[from] ObjectError),
+}
+
+/// A global advertised to clients through `wl_registry`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Global {
+    pub name: GlobalName,
+    pub interface: Box<str>,
+    pub version: u32,
+}
+
+impl Global {
+    /// Returns the `wl_registry::global` event that advertises this global
+    pub fn event(&self) -> GlobalEvent {
+        GlobalEvent::new(self.name, self.interface.as_ref().into(), self.version)
+    }
+}
+
+/// The set of globals advertised to clients, which handles the registry handshake
+///
+/// Globals are assigned increasing names starting from `1`, and names are never reused,
+/// so a client binding a removed global can not bind a newer one by mistake.
+#[derive(Debug)]
+pub struct Registry {
+    globals: Vec<Global>,
+    next_name: u32,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            globals: Vec::new(),
+            next_name: 1,
+        }
+    }
+}
+
+impl Registry {
+    /// Returns a new empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a global implementing `interface` up to `version`
+    ///
+    /// Returns the name assigned to the global
+    pub fn add(&mut self, interface: impl Into<Box<str>>, version: u32) -> GlobalName {
+        let name = GlobalName(self.next_name);
+        self.next_name += 1;
+        self.globals.push(Global {
+            name,
+            interface: interface.into(),
+            version,
+        });
+
+        name
+    }
+
+    /// Removes the global with `name`
+    ///
+    /// Clients should be sent a `wl_registry::global_remove` event with its name.
+    pub fn remove(&mut self, name: GlobalName) -> Option<Global> {
+        let index = self.globals.iter().position(|global| global.name == name)?;
+        Some(self.globals.remove(index))
+    }
+
+    /// Returns the global with `name`
+    pub fn get(&self, name: GlobalName) -> Option<&Global> {
+        self.globals.iter().find(|global| global.name == name)
+    }
+
+    /// Returns an iterator over every global in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &Global> {
+        self.globals.iter()
+    }
+
+    /// Returns the `wl_registry::global` events for every global
+    ///
+    /// These should be sent to a client when it calls `wl_display::get_registry`.
+    pub fn global_events(&self) -> impl Iterator<Item = GlobalEvent> {
+        self.globals.iter().map(Global::event)
+    }
+
+    /// Handles a `wl_registry::bind` of the global `name` to the new object `id`
+    ///
+    /// The interface and version of `id` are checked against the global,
+    /// and the new object is recorded in `map` with `data`.
+    ///
+    /// Returns the bound global
+    pub fn bind<D>(
+        &self,
+        map: &mut ObjectMap<D>,
+        name: GlobalName,
+        id: &CustomNewId,
+        data: D,
+    ) -> Result<&Global, RegistryError> {
+        let global = self.get(name).ok_or(RegistryError::UnknownGlobal(name))?;
+
+        if id.name.as_bytes() != global.interface.as_bytes() {
+            return Err(RegistryError::InterfaceMismatch {
+                name,
+                expected: global.interface.clone(),
+                found: id.name.to_string().into(),
+            });
+        }
+
+        if id.version == 0 || id.version > global.version {
+            return Err(RegistryError::UnsupportedVersion {
+                name,
+                requested: id.version,
+                max: global.version,
+            });
+        }
+
+        map.insert_by_name(id.value, global.interface.clone(), id.version, data)?;
+        Ok(global)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::RawString;
+
+    use super::*;
+
+    fn new_id(interface: &str, version: u32, value: u32) -> CustomNewId {
+        CustomNewId {
+            name: RawString::from(interface),
+            version,
+            value,
+        }
+    }
+
+    #[test]
+    fn advertise_globals() {
+        let mut registry = Registry::new();
+        let compositor = registry.add("wl_compositor", 6);
+        let shm = registry.add("wl_shm", 1);
+        assert_eq!((compositor, shm), (GlobalName(1), GlobalName(2)));
+
+        let events = registry.global_events().collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].wire_len(), 8 + 4 + 20 + 4);
+
+        // names are not reused after a global is removed
+        assert!(registry.remove(compositor).is_some());
+        assert_eq!(registry.add("wl_compositor", 6), GlobalName(3));
+        assert_eq!(registry.iter().count(), 2);
+    }
+
+    #[test]
+    fn bind_global() {
+        let mut registry = Registry::new();
+        let name = registry.add("wl_compositor", 5);
+        let mut map = ObjectMap::new();
+
+        let global = registry
+            .bind(&mut map, name, &new_id("wl_compositor", 4, 3), ())
+            .unwrap();
+        assert_eq!(global.name, name);
+        let object = map.get(3).unwrap();
+        assert_eq!((&*object.interface, object.version), ("wl_compositor", 4));
+
+        let result = registry.bind(&mut map, GlobalName(9), &new_id("wl_compositor", 1, 4), ());
+        assert_eq!(result, Err(RegistryError::UnknownGlobal(GlobalName(9))));
+
+        let result = registry.bind(&mut map, name, &new_id("wl_shm", 1, 4), ());
+        assert!(matches!(
+            result,
+            Err(RegistryError::InterfaceMismatch { .. })
+        ));
+
+        let result = registry.bind(&mut map, name, &new_id("wl_compositor", 6, 4), ());
+        assert!(matches!(
+            result,
+            Err(RegistryError::UnsupportedVersion { max: 5, .. })
+        ));
+
+        let result = registry.bind(&mut map, name, &new_id("wl_compositor", 1, 3), ());
+        assert_eq!(result, Err(ObjectError::AlreadyExists(3).into()));
+    }
+}
@@ -0,0 +1,106 @@
+use std::{fmt::Debug, marker::PhantomData};
+
+use derivative::Derivative;
+
+/// Implemented by generated enums that are marked as a bitfield in the protocol
+pub trait Bitfield: Copy + Into<u32> {
+    /// Every bit that is set by at least one entry of the enum
+    const KNOWN_BITS: u32;
+}
+
+/// A set of bitfield flags that keeps any bits the enum `E` does not know about
+///
+/// Newer clients may set bits that were added in a later version of the enum.
+/// Rejecting them outright breaks forward compatibility, but silently dropping them can hide bugs,
+/// so the unknown bits are kept separately for the caller to decide what to do with them.
+#[derive(Derivative)]
+#[derivative(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Flags<E> {
+    _type: PhantomData<fn() -> E>,
+    known: u32,
+    unknown_bits: u32,
+}
+
+impl<E: Bitfield> Flags<E> {
+    /// Splits the raw `bits` into the bits known by `E` and the unknown remainder
+    pub const fn from_bits(bits: u32) -> Self {
+        Self {
+            _type: PhantomData,
+            known: bits & E::KNOWN_BITS,
+            unknown_bits: bits & !E::KNOWN_BITS,
+        }
+    }
+
+    /// Returns only the bits that are known by `E`
+    pub const fn known(self) -> u32 {
+        self.known
+    }
+
+    /// Returns the bits that are not set by any entry of `E`
+    pub const fn unknown_bits(self) -> u32 {
+        self.unknown_bits
+    }
+
+    /// Returns `true` if any unknown bits were set
+    pub const fn has_unknown(self) -> bool {
+        self.unknown_bits != 0
+    }
+
+    /// Returns all of the original bits, including the unknown ones
+    pub const fn bits(self) -> u32 {
+        self.known | self.unknown_bits
+    }
+
+    /// Returns `true` if every bit of `flag` is set
+    pub fn contains(self, flag: E) -> bool {
+        let flag = flag.into();
+        self.known & flag == flag
+    }
+}
+
+impl<E: Bitfield> From<u32> for Flags<E> {
+    fn from(bits: u32) -> Self {
+        Self::from_bits(bits)
+    }
+}
+
+impl<E> Debug for Flags<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Flags")
+            .field("known", &format_args!("{:#x}", self.known))
+            .field("unknown_bits", &format_args!("{:#x}", self.unknown_bits))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{parse::bitfield, parse_all, protocols::wayland::wl_seat::Capability};
+
+    use super::*;
+
+    #[test]
+    fn split_unknown_bits() {
+        let flags = Flags::<Capability>::from(0b1011);
+        assert_eq!(flags.known(), 0b011);
+        assert_eq!(flags.unknown_bits(), 0b1000);
+        assert_eq!(flags.bits(), 0b1011);
+        assert!(flags.has_unknown());
+
+        assert!(flags.contains(Capability::Pointer));
+        assert!(flags.contains(Capability::Keyboard));
+        assert!(!flags.contains(Capability::Touch));
+        assert_eq!(Capability::KNOWN_BITS, 0b111);
+    }
+
+    #[test]
+    fn parse_flags() {
+        let bytes = 0b1100u32.to_ne_bytes();
+        let mut parser = bitfield::Parser::<Capability>::new();
+        let flags = parse_all(&mut parser, &bytes, &mut VecDeque::new()).unwrap();
+        assert!(flags.contains(Capability::Touch));
+        assert_eq!(flags.unknown_bits(), 0b1000);
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt::Display;
+
+use fixed::{traits::FromFixed, types::I24F8};
+
+/// A signed 24.8 fixed point number, as sent for `fixed` arguments
+///
+/// The raw bits are kept so that integer coordinates can be used without going through a float,
+/// which keeps layout math exact and deterministic.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    /// The fixed point number `0`
+    pub const ZERO: Self = Self(0);
+
+    /// Returns a fixed point number from its raw wire bits
+    pub const fn from_bits(bits: i32) -> Self {
+        Self(bits)
+    }
+
+    /// Returns a fixed point number with the integer value `int`
+    ///
+    /// Values outside the 24 bit integer range wrap, the same as `wl_fixed_from_int`.
+    pub const fn from_int(int: i32) -> Self {
+        Self(int.wrapping_mul(256))
+    }
+
+    /// Returns the raw wire bits
+    pub const fn bits(self) -> i32 {
+        self.0
+    }
+
+    /// Returns the integer part, rounded towards negative infinity
+    ///
+    /// Together with [`frac`](Self::frac) this is exact, as the value is `int_part + frac / 256`.
+    pub const fn int_part(self) -> i32 {
+        self.0 >> 8
+    }
+
+    /// Returns the fractional part in 256ths, which is always positive
+    pub const fn frac(self) -> u8 {
+        (self.0 & 0xFF) as u8
+    }
+
+    /// Returns the integer value rounded towards zero, the same as `wl_fixed_to_int`
+    pub const fn to_int(self) -> i32 {
+        self.0 / 256
+    }
+
+    /// Converts the value to any numeric type supported by the `fixed` crate
+    pub fn to_num<N: FromFixed>(self) -> N {
+        I24F8::from_bits(self.0).to_num()
+    }
+}
+
+impl From<Fixed> for f32 {
+    fn from(value: Fixed) -> Self {
+        value.to_num()
+    }
+}
+
+impl From<Fixed> for f64 {
+    fn from(value: Fixed) -> Self {
+        value.to_num()
+    }
+}
+
+impl Display for Fixed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", I24F8::from_bits(self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_parts() {
+        let table: &[(i32, i32, u8, i32)] = &[
+            (0x0000_0000, 0, 0, 0),
+            (0x0000_0180, 1, 0x80, 1),
+            (0x0000_0100, 1, 0, 1),
+            (-0x0000_0100, -1, 0, -1),
+            (-0x0000_0080, -1, 0x80, 0),
+            (-0x0000_0180, -2, 0x80, -1),
+            (i32::MAX, 8388607, 0xFF, 8388607),
+            (i32::MIN, -8388608, 0, -8388608),
+        ];
+
+        for &(bits, int_part, frac, int) in table {
+            let fixed = Fixed::from_bits(bits);
+            assert_eq!(fixed.int_part(), int_part, "bits {bits:#x}");
+            assert_eq!(fixed.frac(), frac, "bits {bits:#x}");
+            assert_eq!(fixed.to_int(), int, "bits {bits:#x}");
+
+            // the parts always add back up to the exact value
+            let exact = fixed.int_part() as f64 + fixed.frac() as f64 / 256.0;
+            assert_eq!(f64::from(fixed), exact, "bits {bits:#x}");
+        }
+
+        assert_eq!(Fixed::from_int(-3).bits(), -0x300);
+        assert_eq!(Fixed::from_int(-3).to_num::<i64>(), -3);
+        assert_eq!(Fixed::from_bits(0x180).to_string(), "1.5");
+    }
+}
@@ -4,6 +4,7 @@ use derivative::Derivative;
 use derive_more::Display;
 
 use super::RawString;
+use crate::Interface;
 
 #[repr(transparent)]
 #[derive(Derivative, Display)]
@@ -33,6 +34,13 @@ impl<T> NewId<T> {
     }
 }
 
+impl<T: Interface> NewId<T> {
+    /// Returns the wire name of the interface this id references
+    pub const fn interface(self) -> &'static str {
+        T::NAME
+    }
+}
+
 impl<T> Debug for NewId<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct(&format!("NewId<{}>", type_name::<T>()))
@@ -49,6 +57,34 @@ pub struct CustomNewId {
     pub value: u32,
 }
 
+impl CustomNewId {
+    /// Returns the number of bytes this id takes on the wire
+    pub fn wire_len(&self) -> usize {
+        self.name.wire_len() + 8
+    }
+}
+
+/// The name of a global advertised by `wl_registry`
+///
+/// Global names are assigned by the server when a global is created and are not object ids,
+/// so they are kept as a separate type to avoid passing one where the other is expected.
+#[repr(transparent)]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display("{_0}")]
+pub struct GlobalName(pub u32);
+
+impl From<u32> for GlobalName {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl GlobalName {
+    pub const fn value(self) -> u32 {
+        self.0
+    }
+}
+
 #[repr(transparent)]
 #[derive(Derivative, Display)]
 #[derivative(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -77,6 +113,13 @@ impl<T> ObjectId<T> {
     }
 }
 
+impl<T: Interface> ObjectId<T> {
+    /// Returns the wire name of the interface this id references
+    pub const fn interface(self) -> &'static str {
+        T::NAME
+    }
+}
+
 impl<T> Debug for ObjectId<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct(&format!("ObjId<{}>", type_name::<T>()))
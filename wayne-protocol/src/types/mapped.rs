@@ -0,0 +1,107 @@
+use std::{
+    io, mem,
+    os::fd::{AsFd, AsRawFd},
+    ptr::NonNull,
+};
+
+/// A read-only memory mapping of a file descriptor
+///
+/// The memory is unmapped when this is dropped.
+///
+/// # SIGBUS
+/// The file is owned by the client, which can truncate it at any time while it is mapped.
+/// Reading a page of the mapping that is past the new end of the file raises `SIGBUS`,
+/// which kills the process by default. The size is checked when mapping,
+/// but that cannot stop a later truncation, so reading the memory is `unsafe`.
+/// Servers should install a `SIGBUS` handler that recovers from this before reading,
+/// as libwayland does for `wl_shm` pools, or only map fds that have been sealed against shrinking.
+pub struct MappedFd {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+unsafe impl Send for MappedFd {}
+unsafe impl Sync for MappedFd {}
+
+impl Drop for MappedFd {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr.as_ptr() as *mut _, self.len) };
+    }
+}
+
+impl MappedFd {
+    /// Maps the first `size` bytes of `fd` into memory as read-only
+    ///
+    /// This is usually used with the fd and size sent by a client for shared memory.
+    /// The client may still write to the memory while it is mapped,
+    /// so the contents should not be trusted to stay the same between reads.
+    ///
+    /// Returns an error if `size` is zero or larger than the file.
+    pub fn map(fd: impl AsFd, size: usize) -> io::Result<Self> {
+        if size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot map an fd with a size of zero",
+            ));
+        }
+
+        // a mapping past the end of the file would fault as soon as it is read
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+        if unsafe { libc::fstat(fd.as_fd().as_raw_fd(), &mut stat) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if (stat.st_size as u64) < size as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot map more bytes than the file contains",
+            ));
+        }
+
+        // map the fd as private so writes from this process are never shared
+        let ptr = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                size,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                fd.as_fd().as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            ptr: NonNull::new(ptr as *mut u8).unwrap(),
+            len: size,
+        })
+    }
+
+    /// Returns the number of bytes that are mapped
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no bytes are mapped, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a pointer to the start of the mapped memory
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns the mapped memory as a byte slice
+    ///
+    /// # Safety
+    /// The file must not be truncated below [`len`](Self::len) while the slice is used,
+    /// or a `SIGBUS` handler must be installed that recovers from reading past its end.
+    /// See the [type docs](Self) for details.
+    pub unsafe fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
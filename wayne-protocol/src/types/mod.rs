@@ -1,6 +1,12 @@
+pub mod bitfield;
+pub mod fixed;
 pub mod id;
+pub mod mapped;
 pub mod raw_enum;
 pub mod string;
 
+pub use bitfield::{Bitfield, Flags};
+pub use fixed::Fixed;
+pub use mapped::MappedFd;
 pub use raw_enum::RawEnum;
 pub use string::RawString;
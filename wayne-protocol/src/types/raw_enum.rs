@@ -1,3 +1,5 @@
+use std::fmt::{Debug, Display};
+
 use derivative::Derivative;
 
 #[derive(Debug, Derivative)]
@@ -27,12 +29,15 @@ impl<E: TryFrom<u32>> RawEnum<u32, E> {
 }
 
 impl<E: TryFrom<u32>> RawEnum<i32, E> {
+    /// Returns a new raw enum for an enum referenced by an `int` argument
+    ///
+    /// Enum values are always stored as the 32 bits sent over the wire, so the value is
+    /// reinterpreted as a `u32` rather than range checked. This means negative values
+    /// map to the entries with the matching bit pattern. No enums in the core wayland
+    /// protocol are negative, but some extensions use `int` arguments for their enums.
     pub fn from_i32(value: i32) -> Self {
         Self {
-            map: |value| {
-                let value = u32::try_from(*value).ok()?;
-                E::try_from(value).ok()
-            },
+            map: |value| E::try_from(*value as u32).ok(),
             value,
         }
     }
@@ -49,3 +54,94 @@ impl<E: TryFrom<u32>> From<i32> for RawEnum<i32, E> {
         Self::from_i32(value)
     }
 }
+
+impl<I: Display, E: Debug> Display for RawEnum<I, E> {
+    /// Writes the name of the enum entry if it is known, otherwise writes `raw(value)`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.build() {
+            Some(entry) => write!(f, "{entry:?}"),
+            None => write!(f, "raw({})", self.value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Example {
+        Zero,
+        Max,
+    }
+
+    impl TryFrom<u32> for Example {
+        type Error = ();
+        fn try_from(value: u32) -> Result<Self, Self::Error> {
+            match value {
+                0 => Ok(Self::Zero),
+                u32::MAX => Ok(Self::Max),
+                _ => Err(()),
+            }
+        }
+    }
+
+    #[test]
+    fn build_from_uint() {
+        assert_eq!(
+            RawEnum::<u32, Example>::from(0).build(),
+            Some(Example::Zero)
+        );
+        assert_eq!(
+            RawEnum::<u32, Example>::from(u32::MAX).build(),
+            Some(Example::Max)
+        );
+        assert_eq!(RawEnum::<u32, Example>::from(1).build(), None);
+    }
+
+    #[test]
+    fn build_from_int() {
+        assert_eq!(
+            RawEnum::<i32, Example>::from(0).build(),
+            Some(Example::Zero)
+        );
+        assert_eq!(
+            RawEnum::<i32, Example>::from(-1).build(),
+            Some(Example::Max)
+        );
+        assert_eq!(RawEnum::<i32, Example>::from(1).build(), None);
+    }
+
+    #[test]
+    fn display_name() {
+        assert_eq!(RawEnum::<u32, Example>::from(0).to_string(), "Zero");
+        assert_eq!(RawEnum::<i32, Example>::from(-1).to_string(), "Max");
+        assert_eq!(RawEnum::<u32, Example>::from(7).to_string(), "raw(7)");
+        assert_eq!(RawEnum::<i32, Example>::from(-7).to_string(), "raw(-7)");
+    }
+
+    #[test]
+    fn int_roundtrip() {
+        use crate::protocols::wayland::wl_output::Transform;
+
+        // wl_surface::set_buffer_transform references this enum from an int argument
+        let table = [
+            (0, Some(Transform::Normal)),
+            (3, Some(Transform::_270)),
+            (7, Some(Transform::Flipped270)),
+            (8, None),
+            (-1, None),
+            (-8, None),
+            (i32::MIN, None),
+            (i32::MAX, None),
+        ];
+
+        for (value, expected) in table {
+            assert_eq!(
+                RawEnum::<i32, Transform>::from(value).build(),
+                expected,
+                "{value}"
+            );
+        }
+    }
+}
@@ -7,6 +7,80 @@ impl RawString {
     pub fn from_bytes(bytes: Box<[u8]>) -> Self {
         Self(bytes)
     }
+
+    /// Returns the bytes of the string, without the null terminator
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the number of bytes in the string, without the null terminator
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the string has no bytes
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the number of bytes this string takes on the wire
+    ///
+    /// This includes the length prefix, the null terminator, and padding.
+    pub fn wire_len(&self) -> usize {
+        4 + ((self.0.len() + 1 + 3) & !3)
+    }
+
+    /// Returns the number of bytes a string that may be null takes on the wire
+    ///
+    /// A null string is only its zero length prefix, so it takes 4 bytes.
+    pub fn nullable_wire_len(string: Option<&Self>) -> usize {
+        string.map_or(4, Self::wire_len)
+    }
+
+    /// Returns the wire form of this string
+    ///
+    /// This is the length prefix including the null terminator, the bytes,
+    /// the null terminator, and padding to a multiple of 4 bytes.
+    /// The string should not contain any null bytes, as they would be rejected when parsed.
+    ///
+    /// This is never a null string, an empty string is written with a length of 1 for its terminator.
+    /// Use [`nullable_to_wire`](Self::nullable_to_wire) for a string that may be null.
+    pub fn to_wire(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.wire_len());
+        bytes.extend_from_slice(&(self.0.len() as u32 + 1).to_ne_bytes());
+        bytes.extend_from_slice(&self.0);
+        bytes.resize(self.wire_len(), 0);
+        bytes
+    }
+
+    /// Returns the wire form of a string that may be null
+    ///
+    /// A null string is written as a length of 0 with no terminator,
+    /// which is distinct from an empty string.
+    pub fn nullable_to_wire(string: Option<&Self>) -> Vec<u8> {
+        match string {
+            Some(string) => string.to_wire(),
+            None => 0u32.to_ne_bytes().to_vec(),
+        }
+    }
+}
+
+impl From<&str> for RawString {
+    fn from(value: &str) -> Self {
+        Self(value.as_bytes().into())
+    }
+}
+
+impl From<String> for RawString {
+    fn from(value: String) -> Self {
+        Self(value.into_bytes().into_boxed_slice())
+    }
+}
+
+impl From<Vec<u8>> for RawString {
+    fn from(value: Vec<u8>) -> Self {
+        Self(value.into_boxed_slice())
+    }
 }
 
 impl Display for RawString {
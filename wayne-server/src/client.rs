@@ -0,0 +1,183 @@
+use std::{
+    io,
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd},
+        unix::net::UnixStream,
+    },
+};
+
+use thiserror::Error;
+use wayne_stream::{Message, WaylandStream, buffer::ReadBuffer};
+
+use crate::sys::{self, PeerCred, Transport};
+
+pub use wayne_stream::buffer::{DEFAULT_DATA_BYTES, DEFAULT_MAX_FDS};
+
+/// The reason a [`Client`] connection can no longer be read from
+#[derive(Debug, Error)]
+pub enum ClosedReason {
+    #[error("Client closed the connection")]
+    PeerClosed,
+    #[error("Failed to read from client: {_0}")]
+    Error(#[from] io::Error),
+}
+
+/// A client accepted from a [`WaylandSocket`](crate::WaylandSocket)
+///
+/// This wraps the accepted stream in a [`WaylandStream`],
+/// so its messages can be received without dropping down to the raw socket.
+pub struct Client<Data = Box<[u8]>, Ctrl = Box<[u8]>>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    stream: WaylandStream<Data, Ctrl>,
+    credentials: PeerCred,
+}
+
+impl Client {
+    /// Returns a new client for `stream` with a heap allocated read buffer of the default size
+    pub fn new(stream: UnixStream) -> io::Result<Self> {
+        let buffer = ReadBuffer::with_fd_capacity(DEFAULT_DATA_BYTES, DEFAULT_MAX_FDS);
+        Self::with_buffer(stream, buffer)
+    }
+}
+
+impl<Data, Ctrl> Client<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Returns a new client for `stream` that reads its messages into `buffer`
+    ///
+    /// The credentials of the client are captured immediately, as they describe the client when it connected.
+    /// If `stream` is a `SOCK_SEQPACKET` socket, the buffer is switched to datagram mode.
+    pub fn with_buffer(stream: UnixStream, mut buffer: ReadBuffer<Data, Ctrl>) -> io::Result<Self> {
+        let credentials = sys::peer_credentials(&stream)?;
+        if Transport::of(&stream)? == Transport::SeqPacket {
+            buffer.set_datagram(true);
+        }
+
+        Ok(Self {
+            stream: WaylandStream::new(stream, buffer),
+            credentials,
+        })
+    }
+
+    /// Returns the credentials of the client at the time it connected
+    pub fn credentials(&self) -> PeerCred {
+        self.credentials
+    }
+
+    /// Reads as many bytes from the client as possible
+    ///
+    /// Returns `true` if any data was received, or `false` if none was waiting.
+    ///
+    /// Returns a [`ClosedReason`] if the client disconnected or the read failed,
+    /// so a clean disconnect can be told apart from an error when the client is removed.
+    /// Messages that were received before the client disconnected can still be parsed.
+    pub fn receive(&mut self) -> Result<bool, ClosedReason> {
+        match self.stream.read()? {
+            false if self.stream.buffer().is_closed() => Err(ClosedReason::PeerClosed),
+            received => Ok(received),
+        }
+    }
+
+    /// Parses the next [`Message`] received from the client
+    ///
+    /// Returns `None` if there are no full messages waiting
+    pub fn pop_message(&mut self) -> Option<Message<'_>> {
+        self.stream.parse_message()
+    }
+
+    /// Parses the next fd received from the client
+    ///
+    /// Returns `None` if there are no fds waiting
+    pub fn pop_fd(&mut self) -> Option<OwnedFd> {
+        self.stream.parse_fd()
+    }
+
+    /// Returns a reference to the underlying [`WaylandStream`]
+    pub fn stream(&self) -> &WaylandStream<Data, Ctrl> {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the underlying [`WaylandStream`]
+    pub fn stream_mut(&mut self) -> &mut WaylandStream<Data, Ctrl> {
+        &mut self.stream
+    }
+
+    /// Returns the underlying [`WaylandStream`]
+    pub fn into_stream(self) -> WaylandStream<Data, Ctrl> {
+        self.stream
+    }
+}
+
+impl<Data, Ctrl> AsFd for Client<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+impl<Data, Ctrl> AsRawFd for Client<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: Message = Message {
+        object_id: 1,
+        opcode: 1,
+        body: &[2, 0, 0, 0],
+    };
+
+    #[test]
+    fn receive_messages() {
+        let (server, client) = UnixStream::pair().unwrap();
+        let mut server = Client::new(server).unwrap();
+        assert_eq!(server.credentials().pid, unsafe { libc::getpid() });
+
+        // nothing has been sent yet
+        assert!(!server.receive().unwrap());
+        assert!(server.pop_message().is_none());
+
+        wayne_stream::send::send_message(&client, &MESSAGE, &[]).unwrap();
+        wayne_stream::send::send_message(&client, &MESSAGE, &[client.as_fd()]).unwrap();
+        assert!(server.receive().unwrap());
+        assert_eq!(server.pop_message(), Some(MESSAGE));
+        assert_eq!(server.pop_message(), Some(MESSAGE));
+        assert!(server.pop_message().is_none());
+        assert!(server.pop_fd().is_some());
+    }
+
+    #[test]
+    fn closed_reason() {
+        let (server, client) = UnixStream::pair().unwrap();
+        let mut server = Client::new(server).unwrap();
+
+        // messages sent before closing are still received
+        wayne_stream::send::send_message(&client, &MESSAGE, &[]).unwrap();
+        drop(client);
+        assert!(server.receive().unwrap());
+        assert_eq!(server.pop_message(), Some(MESSAGE));
+        assert!(matches!(server.receive(), Err(ClosedReason::PeerClosed)));
+
+        // a failed read is reported as an error instead, here from fds with no room to receive them
+        let (server, client) = UnixStream::pair().unwrap();
+        let mut server = Client::with_buffer(server, ReadBuffer::new([0; 64], [])).unwrap();
+        wayne_stream::send::send_message(&client, &MESSAGE, &[client.as_fd()]).unwrap();
+        assert!(matches!(server.receive(), Err(ClosedReason::Error(_))));
+    }
+}
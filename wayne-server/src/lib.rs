@@ -1,7 +1,10 @@
 pub mod buffer;
+pub mod client;
 pub mod lock;
 pub mod socket;
+pub mod sys;
 
 pub use buffer::Buffer;
+pub use client::{Client, ClosedReason};
 pub use lock::AdvisoryLock;
 pub use socket::WaylandSocket;
@@ -1,13 +1,24 @@
 use std::{
     env::{self, VarError},
     fs, io,
-    os::unix::net::{UnixListener, UnixStream},
+    net::Shutdown,
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+        unix::{
+            fs::DirBuilderExt,
+            net::{UnixListener, UnixStream},
+        },
+    },
     path::PathBuf,
+    time::Duration,
 };
 
 use thiserror::Error;
 
-use crate::AdvisoryLock;
+use crate::{
+    AdvisoryLock, Client,
+    sys::{self, PeerCred, Transport},
+};
 
 #[derive(Debug, Error)]
 pub enum BindError {
@@ -15,6 +26,8 @@ pub enum BindError {
     Io(#[from] io::Error),
     #[error("Failed to get 'XDG_RUNTIME_DIR': {_0}")]
     VarError(#[from] VarError),
+    #[error("Failed to create runtime directory '{}': {source}", path.display())]
+    CreateDir { path: PathBuf, source: io::Error },
     #[error("Failed to bind wayland socket in range 0 to {_0}")]
     InUse(usize),
 }
@@ -24,13 +37,22 @@ pub struct WaylandSocket {
     #[allow(dead_code)]
     lock: AdvisoryLock,
     name: String,
+    transport: Transport,
 }
 
 impl WaylandSocket {
+    pub fn builder() -> WaylandSocketBuilder {
+        WaylandSocketBuilder::new()
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
     pub fn accept(&self) -> io::Result<Option<UnixStream>> {
         match self.listener.accept() {
             Ok((stream, _)) => Ok(Some(stream)),
@@ -39,9 +61,161 @@ impl WaylandSocket {
         }
     }
 
+    /// Accepts a pending client as a [`Client`] with the default read buffer
+    ///
+    /// Returns `None` if there are no clients waiting
+    pub fn accept_client(&self) -> io::Result<Option<Client>> {
+        match self.accept()? {
+            Some(stream) => Client::new(stream).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Waits up to `timeout` for a client to connect, then accepts it
+    ///
+    /// A `timeout` of `None` waits until a client connects.
+    /// For integrating with an event loop, register the socket fd with it and call
+    /// [`accept`](Self::accept) when it becomes readable instead.
+    ///
+    /// Returns `None` if no client connected before the timeout
+    pub fn accept_timeout(&self, timeout: Option<Duration>) -> io::Result<Option<UnixStream>> {
+        if !sys::poll_readable(self.as_fd(), timeout)? {
+            return Ok(None);
+        }
+
+        // another thread may have accepted the client since polling
+        self.accept()
+    }
+
+    /// Accepts a pending client only if `filter` approves of its credentials
+    ///
+    /// If the filter rejects the client, its stream is shut down and `None` is returned.
+    /// Failing to shut down a rejected stream is not an error, as it is closed when dropped regardless.
+    /// This can be used to implement access control, such as only accepting clients of a certain uid.
+    pub fn accept_filtered(
+        &self,
+        filter: impl FnOnce(&PeerCred) -> bool,
+    ) -> io::Result<Option<UnixStream>> {
+        let Some(stream) = self.accept()? else {
+            return Ok(None);
+        };
+
+        let cred = sys::peer_credentials(&stream)?;
+        if !filter(&cred) {
+            log::debug!("Rejected client with pid {} and uid {}", cred.pid, cred.uid);
+            // the client may have already disconnected, and the stream is closed on drop anyway
+            if let Err(e) = stream.shutdown(Shutdown::Both) {
+                log::debug!("Failed to shut down rejected client: {e}");
+            }
+            return Ok(None);
+        }
+
+        Ok(Some(stream))
+    }
+
     pub fn bind(max: usize) -> Result<Self, BindError> {
-        // get the xdg environment variable
-        let xdg_dir: PathBuf = env::var("XDG_RUNTIME_DIR")?.into();
+        Self::builder().bind(max)
+    }
+}
+
+impl AsFd for WaylandSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.listener.as_fd()
+    }
+}
+
+impl AsRawFd for WaylandSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+/// A builder used to configure how a [`WaylandSocket`] is bound
+#[derive(Debug, Clone)]
+pub struct WaylandSocketBuilder {
+    transport: Transport,
+    runtime_dir: Option<PathBuf>,
+    mode: u32,
+    create_dir: bool,
+}
+
+impl Default for WaylandSocketBuilder {
+    fn default() -> Self {
+        Self {
+            transport: Transport::default(),
+            runtime_dir: None,
+            mode: sys::DEFAULT_SOCKET_MODE,
+            create_dir: false,
+        }
+    }
+}
+
+impl WaylandSocketBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the socket type used for the listener and accepted streams
+    ///
+    /// Defaults to [`Transport::Stream`]
+    pub fn transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Sets the directory the socket is bound in
+    ///
+    /// This bypasses the `XDG_RUNTIME_DIR` lookup, which is useful in environments
+    /// without a full session such as minimal containers or CI.
+    ///
+    /// Defaults to the value of `XDG_RUNTIME_DIR`
+    pub fn runtime_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.runtime_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the permissions of the socket file
+    ///
+    /// A socket that other users can write to lets them connect to the compositor,
+    /// so this is applied explicitly instead of relying on the process umask.
+    ///
+    /// Defaults to `0o700`, which only allows the owner to connect
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets whether the runtime directory is created if it does not exist
+    ///
+    /// The directory and any missing parents are created with mode `0o700`,
+    /// which removes a manual setup step in test harnesses and containers with an empty runtime dir.
+    ///
+    /// Defaults to `false`
+    pub fn create_dir(mut self, create: bool) -> Self {
+        self.create_dir = create;
+        self
+    }
+
+    pub fn bind(&self, max: usize) -> Result<WaylandSocket, BindError> {
+        // use the runtime dir override or get the xdg environment variable
+        let xdg_dir: PathBuf = match &self.runtime_dir {
+            Some(dir) => dir.clone(),
+            None => env::var("XDG_RUNTIME_DIR")?.into(),
+        };
+
+        // create the runtime dir if requested
+        if self.create_dir && !xdg_dir.is_dir() {
+            let created = fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(&xdg_dir);
+            if let Err(source) = created {
+                return Err(BindError::CreateDir {
+                    path: xdg_dir,
+                    source,
+                });
+            }
+        }
 
         // create a function to bind a socket path
         let bind_name = |name: String| -> io::Result<WaylandSocket> {
@@ -58,14 +232,14 @@ impl WaylandSocket {
             }
 
             // bind the listener as non-blocking
-            let listener = UnixListener::bind(sock_path)?;
-            listener.set_nonblocking(true)?;
+            let listener = sys::bind_socket(sock_path, self.transport, self.mode)?;
 
             // build and return the socket
             Ok(WaylandSocket {
                 listener,
                 lock,
                 name,
+                transport: self.transport,
             })
         };
 
@@ -92,3 +266,99 @@ impl WaylandSocket {
         Err(BindError::InUse(max))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_runtime_dir() {
+        let dir = env::temp_dir().join(format!("wayne-socket-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let socket = WaylandSocket::builder().runtime_dir(&dir).bind(4).unwrap();
+        assert_eq!(socket.name(), "wayland-0");
+        assert!(dir.join("wayland-0").exists());
+
+        // the next socket should skip the locked name
+        let next = WaylandSocket::builder().runtime_dir(&dir).bind(4).unwrap();
+        assert_eq!(next.name(), "wayland-1");
+
+        drop((socket, next));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn accept_filtered() {
+        use std::io::Read;
+
+        let dir = env::temp_dir().join(format!("wayne-filter-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let socket = WaylandSocket::builder().runtime_dir(&dir).bind(4).unwrap();
+        let path = dir.join(socket.name());
+        let uid = unsafe { libc::getuid() };
+
+        // a rejected client should see its stream closed
+        let mut rejected = UnixStream::connect(&path).unwrap();
+        let accepted = socket.accept_filtered(|cred| cred.uid != uid).unwrap();
+        assert!(accepted.is_none());
+        assert_eq!(rejected.read(&mut [0; 4]).unwrap(), 0);
+
+        let _client = UnixStream::connect(&path).unwrap();
+        let accepted = socket.accept_filtered(|cred| cred.uid == uid).unwrap();
+        assert!(accepted.is_some());
+
+        drop(socket);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn accept_timeout() {
+        let dir = env::temp_dir().join(format!("wayne-timeout-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let socket = WaylandSocket::builder().runtime_dir(&dir).bind(4).unwrap();
+        let timeout = Some(Duration::from_millis(10));
+        assert!(socket.accept_timeout(timeout).unwrap().is_none());
+
+        let _client = UnixStream::connect(dir.join(socket.name())).unwrap();
+        assert!(socket.accept_timeout(timeout).unwrap().is_some());
+
+        let _client = UnixStream::connect(dir.join(socket.name())).unwrap();
+        let client = socket.accept_client().unwrap().unwrap();
+        assert_eq!(client.credentials().uid, unsafe { libc::getuid() });
+        assert!(socket.accept_client().unwrap().is_none());
+
+        drop(socket);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn create_runtime_dir() {
+        let root = env::temp_dir().join(format!("wayne-create-{}", std::process::id()));
+        let dir = root.join("runtime");
+        let _ = fs::remove_dir_all(&root);
+
+        // the directory is only created when requested
+        let result = WaylandSocket::builder().runtime_dir(&dir).bind(1);
+        assert!(matches!(result, Err(BindError::Io(_))));
+        assert!(!dir.exists());
+
+        let socket = WaylandSocket::builder()
+            .runtime_dir(&dir)
+            .create_dir(true)
+            .bind(1)
+            .unwrap();
+        assert!(dir.join(socket.name()).exists());
+
+        let permissions = fs::metadata(&dir).unwrap().permissions();
+        assert_eq!(
+            std::os::unix::fs::PermissionsExt::mode(&permissions) & 0o077,
+            0
+        );
+
+        drop(socket);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
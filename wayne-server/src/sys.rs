@@ -0,0 +1,300 @@
+use std::{
+    fs, io, mem,
+    os::{
+        fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+        unix::{
+            ffi::OsStrExt,
+            fs::PermissionsExt,
+            net::{UnixListener, UnixStream},
+        },
+    },
+    path::Path,
+    time::Duration,
+};
+
+/// The socket type used for a wayland connection
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transport {
+    /// A `SOCK_STREAM` socket, which is what wayland normally uses.
+    ///
+    /// Messages may be split across reads and are reassembled by the read buffer.
+    #[default]
+    Stream,
+    /// A `SOCK_SEQPACKET` socket where the kernel preserves message boundaries.
+    ///
+    /// Each read receives exactly one datagram, so a datagram never contains a partial
+    /// message. The read buffer must be in [datagram mode](wayne_stream::buffer::ReadBuffer::set_datagram)
+    /// so that a datagram is only read once there is space for all of it, which [`Client`](crate::Client)
+    /// enables automatically. A datagram larger than the whole buffer can never be read.
+    SeqPacket,
+}
+
+impl Transport {
+    const fn socket_type(self) -> libc::c_int {
+        match self {
+            Transport::Stream => libc::SOCK_STREAM,
+            Transport::SeqPacket => libc::SOCK_SEQPACKET,
+        }
+    }
+
+    /// Returns the transport used by the connected `stream`
+    pub fn of(stream: &UnixStream) -> io::Result<Self> {
+        let mut ty: libc::c_int = 0;
+        let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+        let ty_ptr = &mut ty as *mut libc::c_int as *mut libc::c_void;
+        let result = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_TYPE,
+                ty_ptr,
+                &mut len,
+            )
+        };
+
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        match ty {
+            libc::SOCK_STREAM => Ok(Transport::Stream),
+            libc::SOCK_SEQPACKET => Ok(Transport::SeqPacket),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "socket is not a stream or seqpacket socket",
+            )),
+        }
+    }
+}
+
+/// The default permissions of a bound socket file, which only allows the owner to connect
+pub const DEFAULT_SOCKET_MODE: u32 = 0o700;
+
+/// Binds a non-blocking unix socket listener to `path` using `transport`
+///
+/// The socket file permissions are set to `mode` before listening, regardless of the process umask.
+/// This ensures no client can connect while the socket has the wrong permissions.
+pub fn bind_socket(
+    path: impl AsRef<Path>,
+    transport: Transport,
+    mode: u32,
+) -> io::Result<UnixListener> {
+    let path = path.as_ref();
+    let (addr, addr_len) = socket_addr(path)?;
+    let fd = socket(transport, libc::SOCK_NONBLOCK)?;
+
+    // bind the socket to the address
+    let addr_ptr = &addr as *const libc::sockaddr_un as *const libc::sockaddr;
+    if unsafe { libc::bind(fd.as_raw_fd(), addr_ptr, addr_len) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // set the socket file mode, as the one created by bind is masked by the umask
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+
+    // start listening for connections
+    if unsafe { libc::listen(fd.as_raw_fd(), libc::SOMAXCONN) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(UnixListener::from(fd))
+}
+
+/// Connects a blocking unix socket stream to the listener at `path` using `transport`
+pub fn connect_socket(path: impl AsRef<Path>, transport: Transport) -> io::Result<UnixStream> {
+    let (addr, addr_len) = socket_addr(path.as_ref())?;
+    let fd = socket(transport, 0)?;
+
+    // connect the socket to the address
+    let addr_ptr = &addr as *const libc::sockaddr_un as *const libc::sockaddr;
+    if unsafe { libc::connect(fd.as_raw_fd(), addr_ptr, addr_len) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(UnixStream::from(fd))
+}
+
+/// The credentials of the process on the other end of a unix socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeerCred {
+    pub pid: libc::pid_t,
+    pub uid: libc::uid_t,
+    pub gid: libc::gid_t,
+}
+
+/// Gets the credentials of the peer connected to `stream`
+///
+/// These are the credentials of the peer at the time it connected, not necessarily its current ones.
+pub fn peer_credentials(stream: &UnixStream) -> io::Result<PeerCred> {
+    let mut cred: libc::ucred = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let cred_ptr = &mut cred as *mut libc::ucred as *mut libc::c_void;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            cred_ptr,
+            &mut len,
+        )
+    };
+
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(PeerCred {
+        pid: cred.pid,
+        uid: cred.uid,
+        gid: cred.gid,
+    })
+}
+
+/// Waits until `fd` is readable or `timeout` elapses
+///
+/// A `timeout` of `None` waits forever. Durations are rounded up to the next millisecond,
+/// and interrupted waits are retried with the full timeout.
+///
+/// Returns `true` if the fd became readable
+pub fn poll_readable(fd: BorrowedFd, timeout: Option<Duration>) -> io::Result<bool> {
+    let timeout = match timeout {
+        None => -1,
+        Some(timeout) => {
+            let millis = timeout.as_nanos().div_ceil(1_000_000);
+            millis.min(libc::c_int::MAX as u128) as libc::c_int
+        }
+    };
+
+    let mut pollfd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    loop {
+        match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+            0 => return Ok(false),
+            result if result > 0 => return Ok(true),
+            _ => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+fn socket(transport: Transport, flags: libc::c_int) -> io::Result<OwnedFd> {
+    let ty = transport.socket_type() | libc::SOCK_CLOEXEC | flags;
+    let fd = unsafe { libc::socket(libc::AF_UNIX, ty, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn socket_addr(path: &Path) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    // build a zeroed socket address
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    // ensure the path fits in the address, failing the same way bind would
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.len() > addr.sun_path.len() {
+        return Err(io::Error::from_raw_os_error(libc::ENAMETOOLONG));
+    }
+
+    // copy the path into the address
+    for (dst, src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+
+    // the address length includes the null terminator if there is space for it
+    let path_offset = mem::offset_of!(libc::sockaddr_un, sun_path);
+    let nul_len = (bytes.len() < addr.sun_path.len()) as usize;
+    let addr_len = (path_offset + bytes.len() + nul_len) as libc::socklen_t;
+    Ok((addr, addr_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, path::PathBuf};
+
+    use super::*;
+
+    #[test]
+    fn seqpacket_preserves_boundaries() {
+        let dir = std::env::temp_dir().join(format!("wayne-sys-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("seqpacket");
+        let _ = fs::remove_file(&path);
+
+        let listener = bind_socket(&path, Transport::SeqPacket, DEFAULT_SOCKET_MODE).unwrap();
+        let mut client = connect_socket(&path, Transport::SeqPacket).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        client.write_all(&[1, 2, 3, 4]).unwrap();
+        client.write_all(&[5, 6, 7, 8]).unwrap();
+
+        assert_eq!(Transport::of(&client).unwrap(), Transport::SeqPacket);
+        assert_eq!(Transport::of(&server).unwrap(), Transport::SeqPacket);
+
+        // each read should only receive a single packet
+        let mut buf = [0u8; 16];
+        let len = unsafe { libc::recv(server.as_raw_fd(), buf.as_mut_ptr() as *mut _, 16, 0) };
+        assert_eq!(&buf[..len as usize], &[1, 2, 3, 4]);
+        let len = unsafe { libc::recv(server.as_raw_fd(), buf.as_mut_ptr() as *mut _, 16, 0) };
+        assert_eq!(&buf[..len as usize], &[5, 6, 7, 8]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pair_credentials() {
+        let (a, _b) = UnixStream::pair().unwrap();
+        let cred = peer_credentials(&a).unwrap();
+        assert_eq!(cred.pid, unsafe { libc::getpid() });
+        assert_eq!(cred.uid, unsafe { libc::getuid() });
+        assert_eq!(cred.gid, unsafe { libc::getgid() });
+    }
+
+    #[test]
+    fn socket_addr_boundary() {
+        let path_offset = mem::offset_of!(libc::sockaddr_un, sun_path);
+        let path = |len| PathBuf::from("a".repeat(len));
+
+        // a path shorter than sun_path keeps its nul terminator
+        let (addr, addr_len) = socket_addr(&path(107)).unwrap();
+        assert_eq!(addr_len as usize, path_offset + 108);
+        assert_eq!(addr.sun_path[107], 0);
+
+        // a path that exactly fills sun_path is sent without one
+        let (addr, addr_len) = socket_addr(&path(108)).unwrap();
+        assert_eq!(addr_len as usize, path_offset + 108);
+        assert_eq!(addr.sun_path[107], b'a' as libc::c_char);
+
+        let Err(error) = socket_addr(&path(109)) else {
+            panic!("a 109 byte path should not fit");
+        };
+        assert_eq!(error.raw_os_error(), Some(libc::ENAMETOOLONG));
+    }
+
+    #[test]
+    fn socket_mode() {
+        let dir = std::env::temp_dir().join(format!("wayne-mode-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for mode in [DEFAULT_SOCKET_MODE, 0o770] {
+            let path = dir.join(format!("mode-{mode:o}"));
+            let _ = fs::remove_file(&path);
+
+            let _listener = bind_socket(&path, Transport::Stream, mode).unwrap();
+            let permissions = fs::metadata(&path).unwrap().permissions();
+            assert_eq!(permissions.mode() & 0o777, mode);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+use std::{mem::MaybeUninit, slice};
+
+/// Uninitialized storage that can back a [`ReadBuffer`](super::ReadBuffer)
+///
+/// Reads hand the buffer to the kernel as a `&mut [u8]`, which must be initialized,
+/// so the storage is zeroed once when the buffer is created.
+/// This lets the storage be allocated without going through a zeroed `[u8]`,
+/// while both [`AsRef`] and [`AsMut`] always report its full length.
+pub struct InitBuffer<B> {
+    buf: B,
+}
+
+impl<B: AsMut<[MaybeUninit<u8>]>> InitBuffer<B> {
+    /// Returns a new buffer backed by the storage `buf`, which is zeroed
+    pub fn new(mut buf: B) -> Self {
+        buf.as_mut().fill(MaybeUninit::new(0));
+        Self { buf }
+    }
+}
+
+impl<B: AsRef<[MaybeUninit<u8>]>> AsRef<[u8]> for InitBuffer<B> {
+    fn as_ref(&self) -> &[u8] {
+        let buf = self.buf.as_ref();
+
+        // SAFETY: every byte was written when the buffer was created
+        unsafe { slice::from_raw_parts(buf.as_ptr() as *const u8, buf.len()) }
+    }
+}
+
+impl<B: AsMut<[MaybeUninit<u8>]>> AsMut<[u8]> for InitBuffer<B> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        let buf = self.buf.as_mut();
+
+        // SAFETY: every byte was written when the buffer was created,
+        // and only initialized bytes can be written through the returned slice
+        unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, buf.len()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use crate::{Message, buffer::ReadBuffer};
+
+    use super::*;
+
+    #[test]
+    fn read_into_uninit() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let data = InitBuffer::new([MaybeUninit::uninit(); 64]);
+        let ctrl = InitBuffer::new([MaybeUninit::uninit(); 64]);
+        assert_eq!(data.as_ref().len(), 64);
+        assert_eq!(data.as_ref(), &[0; 64]);
+
+        let mut buffer = ReadBuffer::new(data, ctrl);
+        assert!(buffer.parse_message().is_none());
+
+        crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+    }
+}
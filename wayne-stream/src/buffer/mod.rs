@@ -1,3 +1,9 @@
+pub mod init;
 pub mod read;
+pub mod write;
 
-pub use read::ReadBuffer;
+pub use init::InitBuffer;
+pub use read::{
+    Credentials, DEFAULT_DATA_BYTES, DEFAULT_MAX_FDS, ReadBuffer, RecvFlags, cmsg_space,
+};
+pub use write::WriteBuffer;
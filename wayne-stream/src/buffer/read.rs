@@ -1,14 +1,36 @@
 use std::{
     io, mem,
     os::{
-        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
         unix::net::UnixStream,
     },
+    time::{Duration, SystemTime},
 };
 
 use crate::Message;
 
+/// The default number of message bytes a [`ReadBuffer`] can hold
+///
+/// This matches the buffer size used by libwayland.
+pub const DEFAULT_DATA_BYTES: usize = 4096;
+
+/// The default number of fds a [`ReadBuffer`] can receive in a single read
+///
+/// This matches the most fds libwayland sends with a single message.
+pub const DEFAULT_MAX_FDS: usize = 28;
+
 /// A buffer that can be used to read wayland messages from a `UnixStream`
+///
+/// # Message framing and fds
+/// Messages are framed by the length in their header, but fds are sent out of band and carry
+/// no reference to the message they belong to. The kernel delivers fds alongside the first byte
+/// of the data they were sent with, so an fd always arrives with or before its message.
+/// Fds are therefore assigned to messages purely by order: each message takes the number of fds
+/// its arguments need from the front of the queue as it is parsed.
+///
+/// If a peer attaches fds to a message that takes none, those fds stay at the front of the queue
+/// and are handed to the next message that does take fds. See [`set_strict_fds`](Self::set_strict_fds)
+/// to detect this instead of silently assigning the wrong fds.
 pub struct ReadBuffer<Data, Ctrl>
 where
     Data: AsRef<[u8]> + AsMut<[u8]>,
@@ -20,6 +42,53 @@ where
     ctrl_start: usize,
     data_end: usize,
     ctrl_end: Option<usize>,
+    fd_index: usize,
+    cloexec: bool,
+    datagram: bool,
+    recv_flags: RecvFlags,
+    credentials: Option<Credentials>,
+    timestamp: Option<SystemTime>,
+    strict_fds: bool,
+    closed: bool,
+}
+
+/// The credentials of a process, sent as `SCM_CREDENTIALS` control data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Credentials {
+    pub pid: libc::pid_t,
+    pub uid: libc::uid_t,
+    pub gid: libc::gid_t,
+}
+
+/// The `msg_flags` reported by the kernel for the most recent read
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecvFlags(libc::c_int);
+
+impl RecvFlags {
+    /// Returns the raw `msg_flags` bits
+    pub const fn bits(self) -> libc::c_int {
+        self.0
+    }
+
+    /// Returns `true` if control data was discarded because the ctrl buffer was too small
+    pub const fn was_control_truncated(self) -> bool {
+        self.0 & libc::MSG_CTRUNC != 0
+    }
+
+    /// Returns `true` if part of a packet was discarded because the data buffer was too small
+    pub const fn was_data_truncated(self) -> bool {
+        self.0 & libc::MSG_TRUNC != 0
+    }
+
+    /// Returns `true` if the read ended a record, which is only reported for `SOCK_SEQPACKET`
+    pub const fn is_end_of_record(self) -> bool {
+        self.0 & libc::MSG_EOR != 0
+    }
+
+    /// Returns `true` if out of band data was received
+    pub const fn is_out_of_band(self) -> bool {
+        self.0 & libc::MSG_OOB != 0
+    }
 }
 
 impl<Data, Ctrl> Drop for ReadBuffer<Data, Ctrl>
@@ -28,12 +97,59 @@ where
     Ctrl: AsRef<[u8]> + AsMut<[u8]>,
 {
     fn drop(&mut self) {
+        // warn about unparsed data during development
+        // this usually means the protocol state was left partially consumed
+        #[cfg(debug_assertions)]
+        if self.pending_bytes() > 0 || self.pending_fds() > 0 {
+            log::warn!(
+                "dropped wayland buffer with {} unparsed bytes and {} unparsed fds",
+                self.pending_bytes(),
+                self.pending_fds(),
+            );
+        }
+
         // ensure all file descriptors are parsed
         // this ensures none are left dangling
         while self.parse_fd().is_some() {}
     }
 }
 
+impl ReadBuffer<Box<[u8]>, Box<[u8]>> {
+    /// Returns a new heap allocated read buffer with space for `data_bytes` bytes
+    /// and at least `max_fds` fds received in a single read
+    ///
+    /// The ctrl buffer is sized using [`cmsg_space`].
+    pub fn with_fd_capacity(data_bytes: usize, max_fds: usize) -> Self {
+        Self::new(
+            vec![0; data_bytes].into_boxed_slice(),
+            vec![0; cmsg_space(max_fds)].into_boxed_slice(),
+        )
+    }
+
+    /// Reallocates the data buffer to hold `data_bytes` bytes
+    ///
+    /// Any pending message data and fds are preserved,
+    /// so this can be used to grow the buffer when a message is too large to fit.
+    ///
+    /// Returns an error if `data_bytes` is smaller than [`pending_bytes`](Self::pending_bytes).
+    pub fn resize_data(&mut self, data_bytes: usize) -> io::Result<()> {
+        if data_bytes < self.pending_bytes() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot shrink the data buffer below the pending data",
+            ));
+        }
+
+        let mut data_buf = vec![0; data_bytes].into_boxed_slice();
+        data_buf[..self.pending_bytes()]
+            .copy_from_slice(&self.data_buf[self.data_start..self.data_end]);
+        self.data_buf = data_buf;
+        self.data_end -= self.data_start;
+        self.data_start = 0;
+        Ok(())
+    }
+}
+
 impl<Data, Ctrl> ReadBuffer<Data, Ctrl>
 where
     Data: AsRef<[u8]> + AsMut<[u8]>,
@@ -48,13 +164,234 @@ where
             ctrl_start: 0,
             data_end: 0,
             ctrl_end: Some(0),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
+        }
+    }
+
+    /// Returns `true` if unclaimed fds are treated as an error
+    pub fn strict_fds(&self) -> bool {
+        self.strict_fds
+    }
+
+    /// Sets whether unclaimed fds are treated as an error
+    ///
+    /// As an fd never arrives after the message it belongs to, once every buffered byte has
+    /// been parsed, any fds that are still queued were not claimed by any message.
+    /// When enabled, the next read fails with [`io::ErrorKind::InvalidData`] if there are
+    /// any [`unclaimed_fds`](Self::unclaimed_fds), and closes them.
+    ///
+    /// This expects the fds for each message to be taken before reading again.
+    /// It is disabled by default.
+    pub fn set_strict_fds(&mut self, strict: bool) {
+        self.strict_fds = strict;
+    }
+
+    /// Returns the number of pending fds that cannot belong to any message
+    ///
+    /// This is only non-zero when every buffered byte has been parsed but fds are still pending.
+    /// Fds that arrive with a partial message are not counted, as that message may still claim them.
+    pub fn unclaimed_fds(&self) -> usize {
+        match self.pending_bytes() {
+            0 => self.pending_fds(),
+            _ => 0,
+        }
+    }
+
+    /// Returns `true` if the buffer reads from a datagram socket such as `SOCK_SEQPACKET`
+    pub fn datagram(&self) -> bool {
+        self.datagram
+    }
+
+    /// Sets whether the buffer reads from a datagram socket such as `SOCK_SEQPACKET`
+    ///
+    /// A datagram is always received whole, and any part that does not fit is discarded by the kernel.
+    /// When enabled, the size of the next datagram is checked before it is read,
+    /// and it is left on the socket until there is space for all of it,
+    /// so a datagram is never split or truncated because the buffer was partly full.
+    ///
+    /// A read that leaves the datagram on the socket returns `false`,
+    /// so pending messages must be parsed before reading again.
+    /// A datagram larger than the whole buffer can never be read, and returns an error.
+    pub fn set_datagram(&mut self, datagram: bool) {
+        self.datagram = datagram;
+    }
+
+    /// Returns `true` if received file descriptors are marked close-on-exec
+    pub fn cloexec(&self) -> bool {
+        self.cloexec
+    }
+
+    /// Sets whether received file descriptors are marked close-on-exec
+    ///
+    /// This is enabled by default using `MSG_CMSG_CLOEXEC`, and should usually stay that way.
+    ///
+    /// # Security
+    /// When disabled, every fd received from a client is inherited by any child process
+    /// this process spawns. That leaks client owned resources into processes that were
+    /// never meant to see them. Only disable this if the process never calls `exec`,
+    /// or if inheriting the received fds is intentional.
+    pub fn set_cloexec(&mut self, cloexec: bool) {
+        self.cloexec = cloexec;
+    }
+
+    /// Resets the buffer to its initial state so it can be reused for a new connection
+    ///
+    /// Any unparsed data is discarded, and **any unparsed fds are closed**.
+    /// The ctrl buffer is zeroed so no stale fds from the previous connection can be
+    /// parsed again. The [`cloexec`](Self::cloexec), [`strict_fds`](Self::strict_fds)
+    /// and [`datagram`](Self::datagram) settings are preserved.
+    pub fn reset(&mut self) {
+        self.clear_fds();
+        self.ctrl_buf.as_mut().fill(0);
+
+        self.data_start = 0;
+        self.data_end = 0;
+        self.recv_flags = RecvFlags(0);
+        self.credentials = None;
+        self.timestamp = None;
+        self.closed = false;
+    }
+
+    /// Closes every pending fd while keeping any pending data
+    ///
+    /// This is useful for error recovery, such as after a protocol error where the pending fds
+    /// no longer belong to any message, without tearing down the whole buffer.
+    ///
+    /// Returns the number of fds that were closed
+    pub fn clear_fds(&mut self) -> usize {
+        let mut count = 0;
+        while self.parse_fd().is_some() {
+            count += 1;
+        }
+
+        // all the ctrl data has been consumed, so it can start from the beginning again
+        self.ctrl_start = 0;
+        self.ctrl_end = Some(0);
+        self.fd_index = 0;
+        count
+    }
+
+    /// Returns the flags reported by the kernel for the most recent read
+    ///
+    /// These are also updated when a read fails because data was truncated,
+    /// so they can be inspected after the error is returned.
+    pub fn recv_flags(&self) -> RecvFlags {
+        self.recv_flags
+    }
+
+    /// Returns the credentials sent with the most recent read that had any
+    ///
+    /// The kernel only sends these when `SO_PASSCRED` is enabled on the socket.
+    /// They describe the sending process at the time each message was sent.
+    pub fn credentials(&self) -> Option<Credentials> {
+        self.credentials
+    }
+
+    /// Returns the kernel receive timestamp of the most recent read that had one
+    ///
+    /// The kernel only sends these when `SO_TIMESTAMPNS` is enabled on the socket,
+    /// and they record when the data arrived at the socket rather than when it was read.
+    /// Linux only sends them for `SOCK_SEQPACKET` unix sockets.
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.timestamp
+    }
+
+    /// Returns `true` if a read found that the peer closed the connection
+    ///
+    /// Reads return `false` both when no data is waiting and when the peer has closed,
+    /// so this can be used to tell them apart. Messages that were already read can still be parsed.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the number of bytes that have been read but not yet parsed into a [`Message`]
+    ///
+    /// If this is non-zero after [`parse_message`](Self::parse_message) returns `None`,
+    /// then a partial message is waiting for more data from the stream
+    pub fn pending_bytes(&self) -> usize {
+        self.data_end - self.data_start
+    }
+
+    /// Returns the number of file descriptors that have been read but not yet parsed
+    pub fn pending_fds(&self) -> usize {
+        let ctrl_end = self.ctrl_end.unwrap_or(self.ctrl_buf.as_ref().len());
+
+        // loop over every cmsg and count the fds stored within
+        let mut count = 0;
+        let mut ctrl_start = self.ctrl_start;
+        loop {
+            // get the section of the ctrl buffer that has remaining ctrl data in it
+            let ctrl = &self.ctrl_buf.as_ref()[ctrl_start..ctrl_end];
+
+            // break if the buffer is not big enough to hold a cmsghdr
+            if ctrl.len() < mem::size_of::<libc::cmsghdr>() {
+                break;
+            }
+
+            // create a pointer from the ctrl buffer and read it as a cmsghdr
+            let cmsg_ptr = ctrl.as_ptr() as *const libc::cmsghdr;
+            let cmsghdr = unsafe { std::ptr::read_unaligned(cmsg_ptr) };
+
+            // break if the cmsg length is invalid
+            if cmsghdr.cmsg_len < mem::size_of::<libc::cmsghdr>() {
+                break;
+            }
+
+            // only count the fds in SCM_RIGHTS messages
+            if is_cmsg(&cmsghdr, libc::SCM_RIGHTS) {
+                count += cmsg_fd_count(&cmsghdr);
+            }
+
+            ctrl_start += cmsg_align(cmsghdr.cmsg_len);
         }
+
+        // remove any fds that were already parsed from the current cmsg
+        count.saturating_sub(self.fd_index)
     }
 
     /// Parse the next [`Message`] from the `Data` buffer
     ///
     /// Returns `None` if there are none left in the buffer
-    pub fn parse_message(&mut self) -> Option<Message> {
+    pub fn parse_message(&mut self) -> Option<Message<'_>> {
+        // get the length of the next message if it has been fully read
+        let message_len = self.next_message_len()?;
+
+        // pad message length to align to multiple of 4 (32 bits)
+        let padded_len = (message_len + 3) & !3;
+
+        // get the section of the data buffer that has remaining message data in it
+        let data = &self.data_buf.as_ref()[self.data_start..self.data_end];
+        let second_word = u32::from_ne_bytes([data[4], data[5], data[6], data[7]]);
+
+        // increment the data start index for the next iteration.
+        // the padding is already known to be in the buffer, so this never passes the end index
+        self.data_start += padded_len;
+
+        // build and return the parsed message
+        Some(Message {
+            object_id: u32::from_ne_bytes([data[0], data[1], data[2], data[3]]),
+            opcode: (second_word & 0xFFFF) as u16,
+            body: &data[8..message_len],
+        })
+    }
+
+    /// Returns `true` if a full [`Message`] is waiting to be parsed
+    pub fn has_message(&self) -> bool {
+        self.next_message_len().is_some()
+    }
+
+    /// Returns the length of the next message if the whole message is in the `Data` buffer
+    ///
+    /// The padding after the message must also have been read, otherwise it would be
+    /// mistaken for the start of the next message once it arrives.
+    fn next_message_len(&self) -> Option<usize> {
         // get the section of the data buffer that has remaining message data in it
         let data = &self.data_buf.as_ref()[self.data_start..self.data_end];
 
@@ -69,30 +406,97 @@ where
         // extract the message length and ensure that it is at least 8 bytes
         let message_len = ((second_word >> 16) as u16).max(8) as usize;
 
-        // pad message length to align to multiple of 4 (32 bits)
-        let padded_len = ((message_len + 3) & !3) as usize;
-
-        // ensure there is enough data for the rest of the message
-        if data.len() < message_len {
+        // ensure there is enough data for the rest of the message and its padding
+        if data.len() < (message_len + 3) & !3 {
             return None;
         }
 
-        // increment the data start index for the next iteration
-        // and ensure the start index never jumps past the end index
-        self.data_start = (self.data_start + padded_len).min(self.data_end);
+        Some(message_len)
+    }
 
-        // build and return the parsed message
-        Some(Message {
-            object_id: u32::from_ne_bytes([data[0], data[1], data[2], data[3]]),
-            opcode: (second_word & 0xFFFF) as u16,
-            body: &data[8..message_len],
-        })
+    /// Parses every [`Message`] in the `Data` buffer, calling `f` for each one
+    ///
+    /// Each message borrows the internal buffer, so only one message can be held at a time.
+    /// This is equivalent to calling [`parse_message`](Self::parse_message) in a loop
+    /// until it returns `None`.
+    ///
+    /// Returns the number of messages that were parsed
+    pub fn for_each_message(&mut self, mut f: impl FnMut(Message)) -> usize {
+        let mut count = 0;
+        while let Some(message) = self.parse_message() {
+            f(message);
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Parses at most `limit` messages from the `Data` buffer, calling `f` for each one
+    ///
+    /// Any remaining messages are left in the buffer. This can be used to round-robin
+    /// between many clients so that a single noisy client cannot starve the others.
+    ///
+    /// Returns `true` if there are more full messages waiting to be parsed
+    pub fn for_each_message_limited(&mut self, limit: usize, mut f: impl FnMut(Message)) -> bool {
+        for _ in 0..limit {
+            let Some(message) = self.parse_message() else {
+                return false;
+            };
+
+            f(message);
+        }
+
+        self.has_message()
     }
 
     /// Parse the next [`OwnedFd`] from the `Ctrl` buffer
     ///
     /// Returns `None` if there are none left in the buffer
     pub fn parse_fd(&mut self) -> Option<OwnedFd> {
+        let raw_fd = self.parse_raw_fd()?;
+        Some(unsafe { OwnedFd::from_raw_fd(raw_fd) })
+    }
+
+    /// Parse the next [`RawFd`] from the `Ctrl` buffer
+    ///
+    /// This is useful for proxies that forward received fds without taking ownership of them.
+    ///
+    /// The caller becomes responsible for the returned fd. It is no longer tracked by this buffer,
+    /// and will leak unless it is closed manually or converted into an [`OwnedFd`].
+    ///
+    /// Returns `None` if there are none left in the buffer
+    pub fn parse_raw_fd(&mut self) -> Option<RawFd> {
+        let raw_fd = self.next_raw_fd()?;
+
+        // increment the fd index for the next iteration
+        self.fd_index += 1;
+        Some(raw_fd)
+    }
+
+    /// Borrows the next fd in the `Ctrl` buffer without parsing it
+    ///
+    /// Returns `None` if there are none left in the buffer
+    pub fn peek_fd(&mut self) -> Option<BorrowedFd<'_>> {
+        let raw_fd = self.next_raw_fd()?;
+        Some(unsafe { BorrowedFd::borrow_raw(raw_fd) })
+    }
+
+    /// Duplicates the next fd in the `Ctrl` buffer without parsing it
+    ///
+    /// The original fd stays in the buffer, so it can still be parsed and forwarded as normal.
+    /// This is useful for proxies that forward the same fd to several destinations.
+    /// The duplicate is always marked close-on-exec.
+    ///
+    /// Returns `None` if there are none left in the buffer
+    pub fn dup_front_fd(&mut self) -> io::Result<Option<OwnedFd>> {
+        match self.peek_fd() {
+            Some(fd) => fd.try_clone_to_owned().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Finds the next [`RawFd`] in the `Ctrl` buffer without incrementing the fd index
+    fn next_raw_fd(&mut self) -> Option<RawFd> {
         let ctrl_end = self.ctrl_end.unwrap_or_else(|| {
             // if there is no calculated end yet, just assume its the max length
             self.ctrl_buf.as_ref().len()
@@ -124,42 +528,114 @@ where
             // pad the cmsg length to the correct alignment
             let align_len = cmsg_align(cmsghdr.cmsg_len);
 
-            // increment the ctrl start index for the next iteration
-            self.ctrl_start += align_len;
+            // credentials and timestamps are captured when they are read, so they can be skipped here
+            if is_cmsg(&cmsghdr, libc::SCM_CREDENTIALS) || is_cmsg(&cmsghdr, libc::SCM_TIMESTAMPNS)
+            {
+                self.ctrl_start += align_len;
+                continue;
+            }
+
+            // ensure the cmsg represents a SCM_RIGHTS file descriptor
+            if !is_cmsg(&cmsghdr, libc::SCM_RIGHTS) {
+                log::warn!(
+                    "parsed non SCM_RIGHTS ctrl message from wayland buffer \
+                    with level {} and type {}",
+                    cmsghdr.cmsg_level,
+                    cmsghdr.cmsg_type,
+                );
+                self.ctrl_start += align_len;
+                continue;
+            }
 
-            // ensure the cmsg_level represents a SCM_RIGHTS file descriptor
-            if cmsghdr.cmsg_level != libc::SCM_RIGHTS {
-                log::warn!("parsed non SCM_RIGHTS ctrl message from wayland buffer");
+            // a single cmsg may hold many fds, so skip to the next
+            // cmsg once all the fds in this one have been parsed
+            let fd_count = cmsg_fd_count(&cmsghdr);
+            if self.fd_index >= fd_count {
+                self.ctrl_start += align_len;
+                self.fd_index = 0;
                 continue;
             }
 
             // load the fd pointer from the cmsg data
-            let fd_ptr = unsafe { cmsg_ptr.offset(1) as *const RawFd };
-            let raw_fd = unsafe { core::ptr::read_unaligned(fd_ptr) };
-
-            // then build and return the owned fd
-            return Some(unsafe { OwnedFd::from_raw_fd(raw_fd) });
+            let fd_ptr = unsafe { (cmsg_ptr.offset(1) as *const RawFd).add(self.fd_index) };
+            return Some(unsafe { core::ptr::read_unaligned(fd_ptr) });
         }
     }
 
     /// Reads as many bytes from `stream` as possible
     ///
     /// Returns `true` if any data was received from the socket
-    pub fn read_from_stream(&mut self, stream: &mut UnixStream) -> io::Result<bool> {
+    pub fn read_from_stream(&mut self, stream: &UnixStream) -> io::Result<bool> {
+        self.read_from_fd(stream.as_fd())
+    }
+
+    /// Reads as many bytes from the unix socket `fd` as possible
+    ///
+    /// This is useful when the socket is not owned as a [`UnixStream`],
+    /// such as one borrowed from an external event loop or passed in through socket activation.
+    ///
+    /// Returns an error if the data buffer is full of a single partial message,
+    /// as the message can never be completed without a larger buffer,
+    /// or if there are unclaimed fds and [`strict_fds`](Self::strict_fds) is enabled.
+    ///
+    /// Returns `true` if any data was received from the socket.
+    /// If the peer closed the connection this returns `false` and [`is_closed`](Self::is_closed) is set.
+    pub fn read_from_fd(&mut self, fd: BorrowedFd) -> io::Result<bool> {
+        // in strict mode, fds left over from fully parsed messages are a protocol error
+        if self.strict_fds && self.unclaimed_fds() > 0 {
+            let count = self.clear_fds();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{count} fds were not claimed by any message"),
+            ));
+        }
+
         // shift both buffers to make space for incoming data
         self.shift_data_buffer();
         self.shift_ctrl_buffer();
 
+        // a full buffer without a complete message can never make progress,
+        // as the rest of the message has nowhere to be read into
+        let full = self.data_end == self.data_buf.as_ref().len();
+        if full && !self.has_message() {
+            return Err(io::Error::other(
+                "data buffer is full, message is larger than the buffer",
+            ));
+        }
+
+        // a buffer full of complete messages has no space to read into until they are parsed.
+        // reading into an empty iov would return 0 as if the peer closed, and could
+        // take fds off the socket without anywhere to keep the bytes they arrived with
+        if full {
+            return Ok(false);
+        }
+
+        // a datagram must be read whole, so wait until there is space for all of it
+        if self.datagram {
+            let Some(packet_len) = peek_packet_len(fd)? else {
+                return Ok(false);
+            };
+
+            let capacity = self.data_buf.as_ref().len();
+            if packet_len > capacity {
+                return Err(io::Error::other("datagram is larger than the data buffer"));
+            }
+
+            if packet_len > capacity - self.data_end {
+                return Ok(false);
+            }
+        }
+
         // calculate the end of the ctrl buffer
-        // this is required so that
+        // this is required so that pending ctrl data is not overwritten
         let ctrl_end = self.calculate_ctrl_end();
 
         // get the empty data and ctrl buffer sections
         let data = &mut self.data_buf.as_mut()[self.data_end..];
         let ctrl = &mut self.ctrl_buf.as_mut()[ctrl_end..];
 
-        // ensure the ctrl buffer is zeroed out
-        ctrl.fill(0);
+        // the ctrl buffer does not need to be zeroed, as the kernel reports exactly
+        // how many ctrl bytes it wrote and only those are ever parsed
 
         // build scatter/gather array with single data buffer
         let msg_iov = &mut [libc::iovec {
@@ -178,15 +654,15 @@ where
             msg_flags: 0,
         };
 
-        // call recvmsg to get data from the client
-        let recv_len = unsafe {
-            libc::recvmsg(
-                stream.as_raw_fd(),
-                (&mut msghdr) as *mut _,
-                libc::MSG_CMSG_CLOEXEC | libc::MSG_DONTWAIT,
-            )
+        // only mark the received fds as close-on-exec if enabled
+        let flags = match self.cloexec {
+            true => libc::MSG_CMSG_CLOEXEC | libc::MSG_DONTWAIT,
+            false => libc::MSG_DONTWAIT,
         };
 
+        // call recvmsg to get data from the client
+        let recv_len = unsafe { libc::recvmsg(fd.as_raw_fd(), (&mut msghdr) as *mut _, flags) };
+
         // try to convert the received length into a valid data length
         let Ok(data_len) = usize::try_from(recv_len) else {
             return match io::Error::last_os_error() {
@@ -196,23 +672,128 @@ where
             };
         };
 
+        // store the flags so they can be inspected by the caller
+        self.recv_flags = RecvFlags(msghdr.msg_flags);
+
+        // the received data is dropped on error, so close any fds that arrived with it,
+        // as they are past the ctrl end and would otherwise never be parsed or closed
+        let new_ctrl_end = ctrl_end + msghdr.msg_controllen;
+        let truncated =
+            self.recv_flags.was_control_truncated() || self.recv_flags.was_data_truncated();
+        if truncated {
+            self.close_fds_between(ctrl_end, new_ctrl_end);
+        }
+
         // ensure no control data was truncated
-        if msghdr.msg_flags & libc::MSG_CTRUNC > 0 {
+        if self.recv_flags.was_control_truncated() {
             return Err(io::Error::other(
                 "ctrl buffer overflow, file descriptors were truncated",
             ));
         }
 
-        // return false if no data was read
+        // ensure no data was truncated
+        // this can only happen with SOCK_SEQPACKET when a packet is larger than the buffer
+        if self.recv_flags.was_data_truncated() {
+            return Err(io::Error::other(
+                "data buffer overflow, packet was truncated",
+            ));
+        }
+
+        // a successful read of no data into a non-empty buffer means the peer closed the connection
         if data_len == 0 {
+            self.closed = msg_iov[0].iov_len > 0;
             return Ok(false);
         }
 
-        // increment the data end and return true
+        // capture any credentials and timestamps that were sent alongside the data
+        self.capture_ancillary(ctrl_end, new_ctrl_end);
+
+        // increment the data and ctrl ends and return true
         self.data_end += data_len;
+        self.ctrl_end = Some(new_ctrl_end);
         Ok(true)
     }
 
+    /// Closes every fd in the ctrl buffer between `start` and `end`
+    fn close_fds_between(&self, mut start: usize, end: usize) {
+        loop {
+            // get the section of the ctrl buffer that has not been closed yet
+            let ctrl = &self.ctrl_buf.as_ref()[start..end];
+
+            // break if the buffer is not big enough to hold a cmsghdr
+            if ctrl.len() < mem::size_of::<libc::cmsghdr>() {
+                break;
+            }
+
+            // create a pointer from the ctrl buffer and read it as a cmsghdr
+            let cmsg_ptr = ctrl.as_ptr() as *const libc::cmsghdr;
+            let cmsghdr = unsafe { std::ptr::read_unaligned(cmsg_ptr) };
+
+            // break if the cmsg length is invalid
+            if cmsghdr.cmsg_len < mem::size_of::<libc::cmsghdr>() {
+                break;
+            }
+
+            if is_cmsg(&cmsghdr, libc::SCM_RIGHTS) {
+                let fd_ptr = unsafe { cmsg_ptr.offset(1) as *const RawFd };
+                for index in 0..cmsg_fd_count(&cmsghdr) {
+                    let raw_fd = unsafe { std::ptr::read_unaligned(fd_ptr.add(index)) };
+                    drop(unsafe { OwnedFd::from_raw_fd(raw_fd) });
+                }
+            }
+
+            start += cmsg_align(cmsghdr.cmsg_len);
+        }
+    }
+
+    /// Stores the last `SCM_CREDENTIALS` and `SCM_TIMESTAMPNS` found in the ctrl buffer
+    /// between `start` and `end`, keeping the previous values if there are none
+    fn capture_ancillary(&mut self, mut start: usize, end: usize) {
+        loop {
+            // get the section of the ctrl buffer that has not been searched yet
+            let ctrl = &self.ctrl_buf.as_ref()[start..end];
+
+            // break if the buffer is not big enough to hold a cmsghdr
+            if ctrl.len() < mem::size_of::<libc::cmsghdr>() {
+                break;
+            }
+
+            // create a pointer from the ctrl buffer and read it as a cmsghdr
+            let cmsg_ptr = ctrl.as_ptr() as *const libc::cmsghdr;
+            let cmsghdr = unsafe { std::ptr::read_unaligned(cmsg_ptr) };
+
+            // break if the cmsg length is invalid
+            if cmsghdr.cmsg_len < mem::size_of::<libc::cmsghdr>() {
+                break;
+            }
+
+            // read the credentials from the cmsg data
+            let data_len = cmsghdr.cmsg_len - mem::size_of::<libc::cmsghdr>();
+            if is_cmsg(&cmsghdr, libc::SCM_CREDENTIALS) && data_len >= mem::size_of::<libc::ucred>()
+            {
+                let cred_ptr = unsafe { cmsg_ptr.offset(1) as *const libc::ucred };
+                let cred = unsafe { std::ptr::read_unaligned(cred_ptr) };
+                self.credentials = Some(Credentials {
+                    pid: cred.pid,
+                    uid: cred.uid,
+                    gid: cred.gid,
+                });
+            }
+
+            // read the timestamp from the cmsg data
+            if is_cmsg(&cmsghdr, libc::SCM_TIMESTAMPNS)
+                && data_len >= mem::size_of::<libc::timespec>()
+            {
+                let time_ptr = unsafe { cmsg_ptr.offset(1) as *const libc::timespec };
+                let time = unsafe { std::ptr::read_unaligned(time_ptr) };
+                let since_epoch = Duration::new(time.tv_sec as u64, time.tv_nsec as u32);
+                self.timestamp = Some(SystemTime::UNIX_EPOCH + since_epoch);
+            }
+
+            start += cmsg_align(cmsghdr.cmsg_len);
+        }
+    }
+
     fn shift_ctrl_buffer(&mut self) {
         // if the start position is at zero, then it is already shifted
         if self.ctrl_start == 0 {
@@ -297,14 +878,52 @@ where
     }
 }
 
+/// Returns the full length of the next datagram waiting on `fd` without reading it
+///
+/// Returns `None` if there is no datagram waiting
+fn peek_packet_len(fd: BorrowedFd) -> io::Result<Option<usize>> {
+    // with MSG_TRUNC the real length of the datagram is returned, even into an empty buffer
+    let flags = libc::MSG_PEEK | libc::MSG_TRUNC | libc::MSG_DONTWAIT;
+    let len = unsafe { libc::recv(fd.as_raw_fd(), core::ptr::null_mut(), 0, flags) };
+    match usize::try_from(len) {
+        Ok(len) => Ok(Some(len)),
+        Err(_) => match io::Error::last_os_error() {
+            e if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            e => Err(e),
+        },
+    }
+}
+
+/// Returns `true` if the cmsg is a socket level message of type `ty`
+const fn is_cmsg(cmsghdr: &libc::cmsghdr, ty: libc::c_int) -> bool {
+    cmsghdr.cmsg_level == libc::SOL_SOCKET && cmsghdr.cmsg_type == ty
+}
+
+const fn cmsg_fd_count(cmsghdr: &libc::cmsghdr) -> usize {
+    (cmsghdr.cmsg_len - mem::size_of::<libc::cmsghdr>()) / mem::size_of::<RawFd>()
+}
+
+/// Returns the number of ctrl buffer bytes needed to send or receive `max_fds` fds in a single cmsg
+///
+/// This is the same as `CMSG_SPACE(max_fds * size_of::<RawFd>())`, and can be used to size
+/// the ctrl buffer of a [`ReadBuffer`] by the number of fds expected rather than in bytes.
+///
+/// The kernel sends fds that arrived with separate writes in separate cmsgs, each with their own
+/// header, and credentials and timestamps take additional space when `SO_PASSCRED`
+/// or `SO_TIMESTAMPNS` are enabled.
+/// So this should be treated as a lower bound when fds may arrive from many writes at once.
+pub const fn cmsg_space(max_fds: usize) -> usize {
+    cmsg_align(mem::size_of::<libc::cmsghdr>()) + cmsg_align(max_fds * mem::size_of::<RawFd>())
+}
+
 const fn cmsg_align(len: usize) -> usize {
     const USIZE_ALIGN: usize = mem::size_of::<usize>() - 1;
-    len + USIZE_ALIGN & !USIZE_ALIGN
+    (len + USIZE_ALIGN) & !USIZE_ALIGN
 }
 
 #[cfg(test)]
 mod tests {
-    use std::os::fd::IntoRawFd;
+    use std::os::fd::{AsFd, IntoRawFd};
 
     use super::*;
 
@@ -313,8 +932,8 @@ mod tests {
 
         // build cmsghdr
         bytes.extend_from_slice(&cmsg_len.to_ne_bytes()); // cmsg_len
-        bytes.extend_from_slice(&libc::SCM_RIGHTS.to_ne_bytes()); // cmsg_level
-        bytes.extend_from_slice(&[0, 0, 0, 0]); // cmsg_type
+        bytes.extend_from_slice(&libc::SOL_SOCKET.to_ne_bytes()); // cmsg_level
+        bytes.extend_from_slice(&libc::SCM_RIGHTS.to_ne_bytes()); // cmsg_type
 
         // insert the file descriptor
         bytes.extend_from_slice(&fd.to_ne_bytes());
@@ -364,6 +983,14 @@ mod tests {
             ctrl_start: 0,
             data_end,
             ctrl_end: Some(0),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
         };
 
         let message = buffer.parse_message().unwrap();
@@ -389,6 +1016,14 @@ mod tests {
             ctrl_start: 0,
             data_end: 0,
             ctrl_end: Some(ctrl_end),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
         };
 
         let fd = buffer.parse_fd().unwrap().into_raw_fd();
@@ -419,6 +1054,14 @@ mod tests {
             ctrl_start: 0,
             data_end,
             ctrl_end: Some(0),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
         };
 
         for _ in 0..COUNT {
@@ -449,6 +1092,14 @@ mod tests {
             ctrl_start: 0,
             data_end: 0,
             ctrl_end: Some(ctrl_end),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
         };
 
         for _ in 0..COUNT {
@@ -477,6 +1128,14 @@ mod tests {
             ctrl_start: 0,
             data_end: 7,
             ctrl_end: Some(0),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
         };
 
         assert!(buffer.parse_message().is_none());
@@ -489,4 +1148,731 @@ mod tests {
 
         assert!(buffer.parse_message().is_none());
     }
+
+    #[test]
+    fn read_fd_from_stream() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let (fd, _peer) = UnixStream::pair().unwrap();
+
+        // send a single byte of data with the fd attached
+        let mut data = [7u8];
+        let mut ctrl =
+            vec![0u8; unsafe { libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) } as usize];
+        let msg_iov = &mut [libc::iovec {
+            iov_base: data.as_mut_ptr() as *mut _,
+            iov_len: data.len(),
+        }];
+        let mut msghdr: libc::msghdr = unsafe { mem::zeroed() };
+        msghdr.msg_iov = msg_iov.as_mut_ptr();
+        msghdr.msg_iovlen = 1;
+        msghdr.msg_control = ctrl.as_mut_ptr() as *mut _;
+        msghdr.msg_controllen = ctrl.len();
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msghdr);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as usize;
+            let fd_ptr = libc::CMSG_DATA(cmsg) as *mut RawFd;
+            core::ptr::write_unaligned(fd_ptr, fd.as_fd().as_raw_fd());
+        }
+        let sent = unsafe { libc::sendmsg(server.as_raw_fd(), &msghdr, 0) };
+        assert_eq!(sent, 1);
+
+        // the received fd must be visible to parse_fd after the read
+        let mut buffer = ReadBuffer::new([0u8; 64], [0u8; 64]);
+        assert!(buffer.read_from_stream(&client).unwrap());
+        assert!(buffer.parse_fd().is_some());
+        assert!(buffer.parse_fd().is_none());
+    }
+
+    #[test]
+    fn parse_partial_padding() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4, 5],
+        };
+
+        const NEXT: Message = Message {
+            object_id: 7,
+            opcode: 1,
+            body: &[],
+        };
+
+        let mut bytes = Vec::new();
+        encode_message(&mut bytes, &MESSAGE);
+        let padded_len = bytes.len();
+        encode_message(&mut bytes, &NEXT);
+
+        // only the message itself has arrived, without its padding
+        let mut buffer = ReadBuffer {
+            data_buf: bytes,
+            ctrl_buf: [],
+            data_start: 0,
+            ctrl_start: 0,
+            data_end: 8 + MESSAGE.body.len(),
+            ctrl_end: Some(0),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
+        };
+
+        assert!(!buffer.has_message());
+        assert!(buffer.parse_message().is_none());
+
+        // once the padding arrives, the following message must still line up
+        buffer.data_end = buffer.data_buf.len();
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert_eq!(buffer.data_start, padded_len);
+        assert_eq!(buffer.parse_message(), Some(NEXT));
+        assert!(buffer.parse_message().is_none());
+    }
+
+    #[test]
+    fn parse_multi_fd_single_cmsg() {
+        const RAW: [RawFd; 3] = [42, 43, 44];
+
+        // build a single cmsghdr containing all the fds
+        let cmsg_len = mem::size_of::<libc::cmsghdr>() + mem::size_of_val(&RAW);
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&cmsg_len.to_ne_bytes());
+        bytes.extend_from_slice(&libc::SOL_SOCKET.to_ne_bytes());
+        bytes.extend_from_slice(&libc::SCM_RIGHTS.to_ne_bytes());
+        for fd in RAW {
+            bytes.extend_from_slice(&fd.to_ne_bytes());
+        }
+        bytes.resize(cmsg_align(cmsg_len), 0);
+        let ctrl_end = bytes.len();
+
+        let mut buffer = ReadBuffer {
+            data_buf: [],
+            ctrl_buf: bytes,
+            data_start: 0,
+            ctrl_start: 0,
+            data_end: 0,
+            ctrl_end: Some(ctrl_end),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
+        };
+
+        for raw in RAW {
+            let fd = buffer.parse_fd().unwrap().into_raw_fd();
+            assert_eq!(fd, raw);
+        }
+
+        assert!(buffer.parse_fd().is_none());
+    }
+
+    #[test]
+    fn parse_fds_between_credentials() {
+        const RAW: [RawFd; 3] = [42, 43, 44];
+
+        // the credentials are filled with values that would be valid fds if misread
+        fn encode_credentials(bytes: &mut Vec<u8>) {
+            let cmsg_len = mem::size_of::<libc::cmsghdr>() + mem::size_of::<libc::ucred>();
+            bytes.extend_from_slice(&cmsg_len.to_ne_bytes());
+            bytes.extend_from_slice(&libc::SOL_SOCKET.to_ne_bytes());
+            bytes.extend_from_slice(&libc::SCM_CREDENTIALS.to_ne_bytes());
+            for value in [5i32, 6, 7] {
+                bytes.extend_from_slice(&value.to_ne_bytes());
+            }
+            bytes.resize(bytes.len() + cmsg_align(cmsg_len) - cmsg_len, 0);
+        }
+
+        let mut bytes = Vec::new();
+        encode_credentials(&mut bytes);
+        for fd in RAW {
+            encode_fd(&mut bytes, fd);
+            encode_credentials(&mut bytes);
+        }
+        let ctrl_end = bytes.len();
+
+        let mut buffer = ReadBuffer {
+            data_buf: [],
+            ctrl_buf: bytes,
+            data_start: 0,
+            ctrl_start: 0,
+            data_end: 0,
+            ctrl_end: Some(ctrl_end),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
+        };
+
+        assert_eq!(buffer.pending_fds(), RAW.len());
+        for raw in RAW {
+            assert_eq!(buffer.parse_raw_fd(), Some(raw));
+        }
+
+        // nothing from the credentials should be returned as an fd
+        assert_eq!(buffer.pending_fds(), 0);
+        assert_eq!(buffer.parse_raw_fd(), None);
+    }
+
+    #[test]
+    fn pending_counts() {
+        const COUNT: usize = 3;
+        const RAW: RawFd = 42;
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4, 5],
+        };
+
+        let mut data = Vec::new();
+        encode_message(&mut data, &MESSAGE);
+        let data_end = data.len();
+
+        let mut ctrl = Vec::new();
+        for _ in 0..COUNT {
+            encode_fd(&mut ctrl, RAW);
+        }
+        let ctrl_end = ctrl.len();
+
+        let mut buffer = ReadBuffer {
+            data_buf: data,
+            ctrl_buf: ctrl,
+            data_start: 0,
+            ctrl_start: 0,
+            data_end: data_end - 4,
+            ctrl_end: Some(ctrl_end),
+            fd_index: 0,
+            cloexec: true,
+            datagram: false,
+            recv_flags: RecvFlags(0),
+            credentials: None,
+            timestamp: None,
+            strict_fds: false,
+            closed: false,
+        };
+
+        // a partial message should remain pending after parsing fails
+        assert!(buffer.parse_message().is_none());
+        assert_eq!(buffer.pending_bytes(), data_end - 4);
+
+        buffer.data_end = data_end;
+        assert!(buffer.parse_message().is_some());
+        assert_eq!(buffer.pending_bytes(), 0);
+
+        for remaining in (0..COUNT).rev() {
+            let _ = buffer.parse_fd().unwrap().into_raw_fd();
+            assert_eq!(buffer.pending_fds(), remaining);
+        }
+    }
+
+    #[test]
+    fn read_cloexec_flag() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        for cloexec in [true, false] {
+            crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+
+            let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+            buffer.set_cloexec(cloexec);
+            assert!(buffer.read_from_stream(&receiver).unwrap());
+            assert!(buffer.parse_message().is_some());
+
+            let fd = buffer.parse_fd().unwrap();
+            let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFD) };
+            assert_eq!(flags & libc::FD_CLOEXEC != 0, cloexec);
+        }
+    }
+
+    #[test]
+    fn datagram_packets_stay_whole() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[0; 16],
+        };
+
+        let mut fds = [0; 2];
+        let result =
+            unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_SEQPACKET, 0, fds.as_mut_ptr()) };
+        assert_eq!(result, 0);
+        let [sender, receiver] = fds.map(|fd| unsafe { UnixStream::from_raw_fd(fd) });
+
+        // each packet is 24 bytes, so only two fit in the buffer at once
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        buffer.set_datagram(true);
+        for _ in 0..3 {
+            crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+        }
+
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.pending_bytes(), 48);
+
+        // the third packet does not fit, so it is left on the socket instead of being truncated
+        assert!(!buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+
+        // a packet larger than the whole buffer can never be read
+        crate::send::send_bytes(&sender, &[0; 128], &[]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).is_err());
+    }
+
+    #[test]
+    fn truncated_fds_are_closed() {
+        use std::io::Read;
+
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let pairs = [(); 3].map(|_| UnixStream::pair().unwrap());
+
+        // only two of the three fds fit in the ctrl buffer
+        let fds = pairs.each_ref().map(|(fd, _)| fd.as_fd());
+        crate::send::send_message(&sender, &MESSAGE, &fds).unwrap();
+        let mut buffer = ReadBuffer::new([0; 64], [0; cmsg_space(2)]);
+        assert!(buffer.read_from_stream(&receiver).is_err());
+        assert!(buffer.recv_flags().was_control_truncated());
+
+        // once every copy of an fd is closed its peer reads the end of the stream,
+        // so a leaked copy would leave the peer waiting instead
+        for (fd, mut peer) in pairs {
+            drop(fd);
+            peer.set_nonblocking(true).unwrap();
+            assert_eq!(peer.read(&mut [0; 4]).unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn full_buffer_not_closed() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4, 5, 6, 7, 8],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        // fill the buffer with exactly four complete messages
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        for _ in 0..4 {
+            crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+        }
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.pending_bytes(), 64);
+
+        // reading again must not look like the peer closed, or take the waiting fd
+        assert!(!buffer.read_from_stream(&receiver).unwrap());
+        assert!(!buffer.is_closed());
+        assert_eq!(buffer.pending_fds(), 0);
+
+        // once the messages are parsed, the last message and its fd can be read
+        for _ in 0..4 {
+            assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        }
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert!(buffer.parse_fd().is_some());
+        assert!(!buffer.is_closed());
+    }
+
+    #[test]
+    fn strict_unclaimed_fds() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        // an fd sent with the first half of a message may still be claimed by it
+        let mut bytes = Vec::new();
+        encode_message(&mut bytes, &MESSAGE);
+        crate::send::send_bytes(&sender, &bytes[..8], &[fd.as_fd()]).unwrap();
+
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        buffer.set_strict_fds(true);
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert!(buffer.parse_message().is_none());
+        assert_eq!(buffer.unclaimed_fds(), 0);
+
+        // once the message is parsed without taking the fd, it belongs to nothing
+        crate::send::send_bytes(&sender, &bytes[8..], &[]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert!(buffer.parse_message().is_some());
+        assert_eq!(buffer.unclaimed_fds(), 1);
+
+        let error = buffer.read_from_stream(&receiver).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(buffer.pending_fds(), 0);
+
+        // without strict mode the fd stays queued for a later message
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+        buffer.set_strict_fds(false);
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert!(buffer.parse_message().is_some());
+        assert!(!buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.unclaimed_fds(), 1);
+    }
+
+    #[test]
+    fn read_dirty_ctrl_buffer() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        // stale bytes in the ctrl buffer should never be parsed as fds
+        let mut buffer = ReadBuffer::new([0; 64], [0xFF; 64]);
+        crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert!(buffer.parse_fd().is_none());
+
+        let mut buffer = ReadBuffer::new([0; 64], [0xFF; 64]);
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert!(buffer.parse_fd().is_some());
+        assert!(buffer.parse_fd().is_none());
+    }
+
+    #[test]
+    fn parse_raw_fd() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+
+        // the raw fd is no longer tracked by the buffer
+        let raw_fd = buffer.parse_raw_fd().unwrap();
+        assert_eq!(buffer.pending_fds(), 0);
+        drop(buffer);
+
+        // so it should still be open after the buffer is dropped
+        assert!(unsafe { libc::fcntl(raw_fd, libc::F_GETFD) } >= 0);
+        drop(unsafe { OwnedFd::from_raw_fd(raw_fd) });
+    }
+
+    #[test]
+    fn ctrl_truncated_flags() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        // a successful read should report no truncation
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert!(!buffer.recv_flags().was_control_truncated());
+        assert!(!buffer.recv_flags().was_data_truncated());
+        assert!(buffer.parse_message().is_some());
+
+        // a ctrl buffer too small to hold a cmsghdr will always truncate
+        let mut buffer = ReadBuffer::new([0; 64], [0; 4]);
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).is_err());
+        assert!(buffer.recv_flags().was_control_truncated());
+        assert!(!buffer.recv_flags().was_data_truncated());
+    }
+
+    #[test]
+    fn dup_front_fd() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+
+        // the duplicate should be a new fd, leaving the original in the buffer
+        let dup = buffer.dup_front_fd().unwrap().unwrap();
+        assert_eq!(buffer.pending_fds(), 1);
+
+        let original = buffer.parse_fd().unwrap();
+        assert_ne!(dup.as_raw_fd(), original.as_raw_fd());
+        assert!(buffer.dup_front_fd().unwrap().is_none());
+    }
+
+    #[test]
+    fn read_credentials() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+
+        // credentials are only sent when requested by the receiver
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.credentials(), None);
+        assert!(buffer.parse_message().is_some());
+
+        let enable: libc::c_int = 1;
+        let result = unsafe {
+            libc::setsockopt(
+                receiver.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PASSCRED,
+                &enable as *const libc::c_int as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        assert_eq!(result, 0);
+
+        crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        let credentials = buffer.credentials().unwrap();
+        assert_eq!(credentials.pid, unsafe { libc::getpid() });
+        assert_eq!(credentials.uid, unsafe { libc::getuid() });
+
+        // the credentials should never be parsed as fds
+        assert!(buffer.parse_message().is_some());
+        assert!(buffer.parse_fd().is_none());
+    }
+
+    #[test]
+    fn reset_reuse() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+
+        // the first connection leaves an unparsed message and fd behind
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.pending_fds(), 1);
+
+        buffer.reset();
+        assert_eq!(buffer.pending_bytes(), 0);
+        assert_eq!(buffer.pending_fds(), 0);
+        assert!(buffer.ctrl_buf.iter().all(|&byte| byte == 0));
+
+        // the second connection should only see its own messages
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert!(buffer.parse_message().is_none());
+        assert!(buffer.parse_fd().is_none());
+    }
+
+    #[test]
+    fn read_from_fd() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let receiver = OwnedFd::from(receiver);
+        crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        assert!(buffer.read_from_fd(receiver.as_fd()).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert!(!buffer.read_from_fd(receiver.as_fd()).unwrap());
+    }
+
+    #[test]
+    fn fd_capacity() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+        let fds = [fd.as_fd(); 8];
+
+        // exactly enough space for every fd
+        let mut buffer = ReadBuffer::with_fd_capacity(64, 8);
+        crate::send::send_message(&sender, &MESSAGE, &fds).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.pending_fds(), 8);
+        buffer.reset();
+
+        // the space is padded to the alignment of usize, so 7 fds take the same space as 8
+        let mut buffer = ReadBuffer::with_fd_capacity(64, 6);
+        crate::send::send_message(&sender, &MESSAGE, &fds).unwrap();
+        assert!(buffer.read_from_stream(&receiver).is_err());
+        assert!(buffer.recv_flags().was_control_truncated());
+    }
+
+    #[test]
+    fn message_larger_than_buffer() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[0; 16],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        crate::send::send_message(&sender, &MESSAGE, &[]).unwrap();
+
+        // the first read fills the buffer with part of the message
+        let mut buffer = ReadBuffer::new([0; 16], [0; 64]);
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert!(buffer.parse_message().is_none());
+
+        // the next read should fail instead of stalling forever
+        assert!(buffer.read_from_stream(&receiver).is_err());
+    }
+
+    #[test]
+    fn byte_fragmentation() {
+        use std::io::Write;
+
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let mut bytes = Vec::new();
+        encode_message(&mut bytes, &MESSAGE);
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+
+        // the buffer only has space for a single message, so the data must be shifted between messages
+        let mut buffer = ReadBuffer::new([0; 16], [0; 64]);
+        for _ in 0..2 {
+            for (i, byte) in bytes.iter().enumerate() {
+                // nothing should parse until the final byte arrives, including a split header
+                assert_eq!(buffer.parse_message(), None, "byte {i}");
+                sender.write_all(&[*byte]).unwrap();
+                assert!(buffer.read_from_stream(&receiver).unwrap());
+                assert_eq!(buffer.pending_bytes(), i + 1);
+            }
+
+            assert_eq!(buffer.parse_message(), Some(MESSAGE));
+            assert_eq!(buffer.pending_bytes(), 0);
+        }
+    }
+
+    #[test]
+    fn resize_data() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[0; 16],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+
+        // the first read only receives part of the message
+        let mut buffer = ReadBuffer::with_fd_capacity(16, 1);
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert!(buffer.parse_message().is_none());
+
+        // shrinking below the pending data should fail
+        assert!(buffer.resize_data(8).is_err());
+        assert_eq!(buffer.pending_bytes(), 16);
+
+        // after growing, the rest of the message can be read without losing anything
+        buffer.resize_data(MESSAGE.wire_len()).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert!(buffer.parse_fd().is_some());
+    }
+
+    #[test]
+    fn cmsg_space_matches_libc() {
+        for count in 0..=32 {
+            let expected = unsafe { libc::CMSG_SPACE((count * mem::size_of::<RawFd>()) as u32) };
+            assert_eq!(cmsg_space(count), expected as usize, "{count} fds");
+        }
+    }
+
+    #[test]
+    fn clear_fds() {
+        const MESSAGE: Message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &[1, 2, 3, 4],
+        };
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd(), fd.as_fd()]).unwrap();
+
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.clear_fds(), 2);
+        assert_eq!(buffer.pending_fds(), 0);
+
+        // the pending message should be untouched
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+
+        // new fds should still be received after clearing
+        crate::send::send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert!(buffer.parse_fd().is_some());
+        assert!(buffer.parse_fd().is_none());
+    }
 }
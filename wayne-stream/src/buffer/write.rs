@@ -0,0 +1,195 @@
+use std::{
+    io,
+    os::{
+        fd::{AsFd, BorrowedFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+};
+
+use crate::{Message, send};
+
+/// A buffer that can be used to queue wayland messages for writing to a `UnixStream`
+#[derive(Debug, Default)]
+pub struct WriteBuffer {
+    data: Vec<u8>,
+    fds: Vec<OwnedFd>,
+}
+
+impl WriteBuffer {
+    /// Returns a new empty write buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a new empty write buffer with space for at least `bytes` and `fds`
+    ///
+    /// The number of bytes needed for a message can be found using [`Message::wire_len`]
+    pub fn with_capacity(bytes: usize, fds: usize) -> Self {
+        Self {
+            data: Vec::with_capacity(bytes),
+            fds: Vec::with_capacity(fds),
+        }
+    }
+
+    /// Reserves space for at least `bytes` more bytes and `fds` more fds
+    pub fn reserve(&mut self, bytes: usize, fds: usize) {
+        self.data.reserve(bytes);
+        self.fds.reserve(fds);
+    }
+
+    /// Returns the number of bytes waiting to be written
+    pub fn pending_bytes(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the number of fds waiting to be written
+    pub fn pending_fds(&self) -> usize {
+        self.fds.len()
+    }
+
+    /// Returns `true` if there is nothing waiting to be written
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.fds.is_empty()
+    }
+
+    /// Serializes `message` into the buffer and queues `fds` to be sent alongside it
+    ///
+    /// Returns an [`io::ErrorKind::InvalidInput`] error without queueing anything if the message
+    /// is longer than [`MAX_MESSAGE_LEN`](crate::message::MAX_MESSAGE_LEN). The fds are closed.
+    pub fn push_message(
+        &mut self,
+        message: &Message,
+        fds: impl IntoIterator<Item = OwnedFd>,
+    ) -> io::Result<()> {
+        message.write_to(&mut self.data)?;
+        self.fds.extend(fds);
+        Ok(())
+    }
+
+    /// Writes as many queued bytes to `stream` as possible
+    ///
+    /// Queued fds are sent with the first byte that is written, up to [`SCM_MAX_FD`](send::SCM_MAX_FD) at a time.
+    /// While more fds than that are queued, each send only writes a single byte,
+    /// so the remaining fds still go out with the bytes that follow and arrive before their messages.
+    ///
+    /// Returns `true` if the buffer was fully written
+    pub fn write_to_stream(&mut self, stream: &UnixStream) -> io::Result<bool> {
+        while !self.data.is_empty() {
+            // encode as many queued fds as the kernel accepts into the ctrl data
+            let batch = self.fds.len().min(send::SCM_MAX_FD);
+            let fds = self.fds[..batch].iter().map(|fd| fd.as_fd());
+            let ctrl = send::encode_fds(&fds.collect::<Box<[BorrowedFd]>>());
+
+            // hold back the rest of the data while fds are left over, so they have bytes to be sent with
+            let len = match self.fds.len() > batch {
+                true => 1,
+                false => self.data.len(),
+            };
+
+            let sent = match send::sendmsg(stream, &self.data[..len], &ctrl) {
+                Ok(sent) => sent,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e),
+            };
+
+            // once sent the kernel holds its own copies of the fds,
+            // so the queued ones can be closed and the sent bytes removed
+            self.fds.drain(..batch);
+            self.data.drain(..sent);
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::ReadBuffer;
+
+    use super::*;
+
+    const MESSAGE: Message = Message {
+        object_id: 42,
+        opcode: 69,
+        body: &[1, 2, 3, 4, 5],
+    };
+
+    #[test]
+    fn reserve_and_write() {
+        const COUNT: usize = 3;
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        let mut buffer = WriteBuffer::with_capacity(MESSAGE.wire_len(), 1);
+        buffer.push_message(&MESSAGE, [OwnedFd::from(fd)]).unwrap();
+
+        buffer.reserve(MESSAGE.wire_len() * (COUNT - 1), 0);
+        let capacity = buffer.data.capacity();
+        for _ in 1..COUNT {
+            buffer.push_message(&MESSAGE, []).unwrap();
+        }
+
+        // the buffer should never have needed to grow
+        assert_eq!(buffer.data.capacity(), capacity);
+        assert_eq!(buffer.pending_bytes(), MESSAGE.wire_len() * COUNT);
+        assert_eq!(buffer.pending_fds(), 1);
+
+        assert!(buffer.write_to_stream(&sender).unwrap());
+        assert!(buffer.is_empty());
+
+        let mut read = ReadBuffer::new([0; 64], [0; 64]);
+        assert!(read.read_from_stream(&receiver).unwrap());
+        for _ in 0..COUNT {
+            assert_eq!(read.parse_message(), Some(MESSAGE));
+        }
+        assert!(read.parse_fd().is_some());
+    }
+
+    #[test]
+    fn reject_long_message() {
+        let body = vec![0; crate::message::MAX_MESSAGE_LEN - 7];
+        let message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &body,
+        };
+
+        let mut buffer = WriteBuffer::new();
+        let error = buffer.push_message(&message, []).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn write_max_fds() {
+        const COUNT: usize = 26;
+        const FDS: usize = 10;
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        // more fds are queued than can be sent in a single sendmsg
+        let mut buffer = WriteBuffer::new();
+        for _ in 0..COUNT {
+            let fds = (0..FDS).map(|_| OwnedFd::from(fd.try_clone().unwrap()));
+            buffer.push_message(&MESSAGE, fds).unwrap();
+        }
+        assert!(buffer.pending_fds() > send::SCM_MAX_FD);
+
+        assert!(buffer.write_to_stream(&sender).unwrap());
+        assert!(buffer.is_empty());
+
+        // every message should arrive with all of its fds
+        let mut read = ReadBuffer::new([0; 1024], [0; 4096]);
+        let mut messages = 0;
+        while messages < COUNT {
+            assert!(read.read_from_stream(&receiver).unwrap());
+            while let Some(message) = read.parse_message() {
+                assert_eq!(message, MESSAGE);
+                messages += 1;
+            }
+        }
+        assert_eq!(read.pending_fds(), COUNT * FDS);
+    }
+}
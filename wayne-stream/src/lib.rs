@@ -1,4 +1,9 @@
 pub mod buffer;
 pub mod message;
+pub mod send;
+pub mod stream;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
-pub use message::Message;
+pub use message::{Message, OwnedMessage};
+pub use stream::WaylandStream;
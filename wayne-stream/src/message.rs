@@ -1,3 +1,10 @@
+use std::io;
+
+/// The largest length a message can have, including its 8 byte header
+///
+/// The length is sent as a 16 bit field in the header, so longer messages cannot be framed.
+pub const MAX_MESSAGE_LEN: usize = u16::MAX as usize;
+
 /// A raw wayland message before it has been parsed into a protocol item
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Message<'a> {
@@ -5,3 +12,73 @@ pub struct Message<'a> {
     pub opcode: u16,
     pub body: &'a [u8],
 }
+
+impl Message<'_> {
+    /// Returns the number of bytes this message occupies on the wire
+    ///
+    /// This includes the 8 byte header and any padding needed to align the body to 4 bytes
+    pub const fn wire_len(&self) -> usize {
+        (8 + self.body.len() + 3) & !3
+    }
+
+    /// Serializes this message in its wire format to the end of `bytes`
+    ///
+    /// Returns an [`io::ErrorKind::InvalidInput`] error without writing anything
+    /// if the message is longer than [`MAX_MESSAGE_LEN`], as its length would not fit in the header.
+    pub fn write_to(&self, bytes: &mut Vec<u8>) -> io::Result<()> {
+        let message_len = 8 + self.body.len();
+        if message_len > MAX_MESSAGE_LEN {
+            return Err(too_long(message_len));
+        }
+
+        // build the second word
+        let second_word = (self.opcode as u32) | ((message_len as u32) << 16);
+
+        // insert the message data
+        bytes.extend_from_slice(&self.object_id.to_ne_bytes());
+        bytes.extend_from_slice(&second_word.to_ne_bytes());
+        bytes.extend_from_slice(self.body);
+
+        // pad to length
+        let padding = self.wire_len() - message_len;
+        bytes.extend(std::iter::repeat_n(0, padding));
+        Ok(())
+    }
+}
+
+/// Returns the error for a message of `len` bytes that is longer than [`MAX_MESSAGE_LEN`]
+pub(crate) fn too_long(len: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("message length {len} is longer than the maximum of {MAX_MESSAGE_LEN}"),
+    )
+}
+
+/// A [`Message`] that owns its body, so it can be held after the read buffer moves on
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OwnedMessage {
+    pub object_id: u32,
+    pub opcode: u16,
+    pub body: Box<[u8]>,
+}
+
+impl OwnedMessage {
+    /// Returns a [`Message`] that borrows this message's body
+    pub fn as_message(&self) -> Message<'_> {
+        Message {
+            object_id: self.object_id,
+            opcode: self.opcode,
+            body: &self.body,
+        }
+    }
+}
+
+impl From<Message<'_>> for OwnedMessage {
+    fn from(message: Message<'_>) -> Self {
+        Self {
+            object_id: message.object_id,
+            opcode: message.opcode,
+            body: message.body.into(),
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    fmt::Display,
+    io, mem,
+    os::{
+        fd::{AsRawFd, BorrowedFd, RawFd},
+        unix::net::UnixStream,
+    },
+};
+
+use crate::{Message, buffer::cmsg_space};
+
+/// The maximum number of fds the kernel accepts in a single `sendmsg`
+///
+/// Sending more than this fails with `EINVAL`, so larger sets of fds must be split across sends.
+pub const SCM_MAX_FD: usize = 253;
+
+/// An error from a send that may have already written part of the data
+///
+/// The stream is non-blocking, so the socket buffer can fill up partway through a send.
+/// The bytes that were already written cannot be taken back, so `sent` must be used to resume
+/// from the same position, or the framing of every following message will be corrupted.
+/// Any fds were attached to the first byte, so they were sent if `sent` is non-zero.
+///
+/// For a stream that should not block on a slow peer, queue messages in a
+/// [`WriteBuffer`](crate::buffer::WriteBuffer) instead, which keeps track of this itself.
+#[derive(Debug)]
+pub struct SendError {
+    /// The number of bytes that were written before the error
+    pub sent: usize,
+    pub error: io::Error,
+}
+
+impl SendError {
+    /// Returns `true` if some but not all of the data was written
+    pub fn is_partial(&self) -> bool {
+        self.sent > 0
+    }
+}
+
+impl Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} after sending {} bytes", self.error, self.sent)
+    }
+}
+
+impl std::error::Error for SendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<SendError> for io::Error {
+    fn from(error: SendError) -> Self {
+        match error.sent {
+            0 => error.error,
+            _ => io::Error::new(error.error.kind(), error),
+        }
+    }
+}
+
+/// Sends a single [`Message`] to `stream` with `fds` attached
+///
+/// The fds are only borrowed. When they are sent, the kernel installs a new copy of
+/// each fd in the receiving process, so the caller keeps ownership of the originals.
+///
+/// If this fails partway through, the rest of the message can be sent by serializing it again
+/// and passing the unsent bytes to [`send_bytes`] without any fds. See [`SendError`].
+///
+/// Returns the number of bytes written to the stream, or an [`io::ErrorKind::InvalidInput`] error
+/// without sending anything if the message is longer than [`MAX_MESSAGE_LEN`](crate::message::MAX_MESSAGE_LEN)
+pub fn send_message(
+    stream: &UnixStream,
+    message: &Message,
+    fds: &[BorrowedFd],
+) -> Result<usize, SendError> {
+    let mut bytes = Vec::with_capacity(message.wire_len());
+    message
+        .write_to(&mut bytes)
+        .map_err(|error| SendError { sent: 0, error })?;
+    send_bytes(stream, &bytes, fds)
+}
+
+/// Sends the same [`Message`] to every stream in `streams`
+///
+/// The message is serialized once and then sent to each stream in turn.
+/// A failure to send to one stream does not stop the broadcast, instead each error is
+/// collected alongside the index of the stream that produced it.
+/// A partial send is also an error, and its [`SendError::sent`] count must be used to finish
+/// sending to that stream before anything else is sent to it.
+///
+/// # File descriptors
+/// `sendmsg` does not transfer ownership of the attached fds. The kernel duplicates
+/// each fd into the receiving process on every send, so each stream receives its own
+/// copy and the borrowed `fds` stay valid for the whole broadcast without any `dup` calls.
+///
+/// A message longer than [`MAX_MESSAGE_LEN`](crate::message::MAX_MESSAGE_LEN) is not sent to any stream,
+/// and every stream gets an [`io::ErrorKind::InvalidInput`] error.
+pub fn broadcast(
+    streams: &[UnixStream],
+    message: &Message,
+    fds: &[BorrowedFd],
+) -> Vec<(usize, SendError)> {
+    let mut bytes = Vec::with_capacity(message.wire_len());
+    if message.write_to(&mut bytes).is_err() {
+        let error = |index| {
+            let error = crate::message::too_long(8 + message.body.len());
+            (index, SendError { sent: 0, error })
+        };
+        return (0..streams.len()).map(error).collect();
+    }
+
+    let mut errors = Vec::new();
+    for (index, stream) in streams.iter().enumerate() {
+        if let Err(e) = send_bytes(stream, &bytes, fds) {
+            log::debug!("failed to broadcast message to stream {index}: {e}");
+            errors.push((index, e));
+        }
+    }
+
+    errors
+}
+
+/// Sends all of `bytes` to `stream` with `fds` attached to the first byte
+///
+/// Fds can only be sent alongside data, so `bytes` must not be empty if there are any `fds`.
+///
+/// Returns the number of bytes written to the stream,
+/// or a [`SendError`] with the number of bytes written before the failure
+pub fn send_bytes(
+    stream: &UnixStream,
+    bytes: &[u8],
+    fds: &[BorrowedFd],
+) -> Result<usize, SendError> {
+    if bytes.is_empty() && !fds.is_empty() {
+        return Err(SendError {
+            sent: 0,
+            error: io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "fds can only be sent with at least one byte of data",
+            ),
+        });
+    }
+
+    // build the ctrl buffer containing the fds
+    let ctrl = encode_fds(fds);
+
+    // keep sending until all the bytes have been written
+    let mut sent = 0;
+    while sent < bytes.len() {
+        // only attach the ctrl data to the first send
+        let ctrl = match sent {
+            0 => ctrl.as_slice(),
+            _ => &[],
+        };
+
+        sent += sendmsg(stream, &bytes[sent..], ctrl).map_err(|error| SendError { sent, error })?;
+    }
+
+    Ok(sent)
+}
+
+/// Calls `sendmsg` once with `bytes` and the encoded `ctrl` data
+///
+/// Running out of kernel buffer space with `ENOBUFS` is reported as [`io::ErrorKind::WouldBlock`],
+/// as it clears once the peer reads and should be retried later like `EAGAIN`. This is more likely
+/// with fds attached, as each one in flight takes additional kernel memory.
+/// `EMSGSIZE` can never succeed on a retry, so it is reported as a hard error.
+///
+/// Returns the number of bytes that were sent
+pub(crate) fn sendmsg(stream: &UnixStream, bytes: &[u8], ctrl: &[u8]) -> io::Result<usize> {
+    // build scatter/gather array with single data buffer
+    let msg_iov = &mut [libc::iovec {
+        iov_base: bytes.as_ptr() as *mut _,
+        iov_len: bytes.len(),
+    }];
+
+    // only attach the ctrl data if there is any
+    let (msg_control, msg_controllen) = match ctrl.is_empty() {
+        false => (ctrl.as_ptr() as *mut _, ctrl.len()),
+        true => (core::ptr::null_mut(), 0),
+    };
+
+    // build msghdr for the send call
+    let msghdr = libc::msghdr {
+        msg_name: core::ptr::null_mut(),
+        msg_namelen: 0,
+        msg_iov: msg_iov.as_mut_ptr(),
+        msg_iovlen: 1,
+        msg_control,
+        msg_controllen,
+        msg_flags: 0,
+    };
+
+    // call sendmsg to send the data to the client
+    let send_len = unsafe {
+        libc::sendmsg(
+            stream.as_raw_fd(),
+            &msghdr as *const _,
+            libc::MSG_NOSIGNAL | libc::MSG_DONTWAIT,
+        )
+    };
+
+    // try to convert the sent length into a valid data length
+    usize::try_from(send_len).map_err(|_| {
+        let error = io::Error::last_os_error();
+        match error.raw_os_error() {
+            Some(libc::ENOBUFS) => io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "no kernel buffer space available, try again later",
+            ),
+            Some(libc::EMSGSIZE) => io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "message is too large to fit in the socket send buffer",
+            ),
+            _ => error,
+        }
+    })
+}
+
+pub(crate) fn encode_fds(fds: &[BorrowedFd]) -> Vec<u8> {
+    if fds.is_empty() {
+        return Vec::new();
+    }
+
+    // allocate a zeroed ctrl buffer large enough for a single cmsg
+    let cmsg_len = mem::size_of::<libc::cmsghdr>() + mem::size_of_val(fds);
+    let mut ctrl = vec![0u8; cmsg_space(fds.len())];
+
+    // write the cmsghdr to the start of the ctrl buffer
+    let cmsg_ptr = ctrl.as_mut_ptr() as *mut libc::cmsghdr;
+    let cmsghdr = libc::cmsghdr {
+        cmsg_len,
+        cmsg_level: libc::SOL_SOCKET,
+        cmsg_type: libc::SCM_RIGHTS,
+    };
+    unsafe { core::ptr::write_unaligned(cmsg_ptr, cmsghdr) };
+
+    // then write all the raw fds directly after it
+    let fd_ptr = unsafe { cmsg_ptr.offset(1) as *mut RawFd };
+    for (index, fd) in fds.iter().enumerate() {
+        unsafe { core::ptr::write_unaligned(fd_ptr.add(index), fd.as_raw_fd()) };
+    }
+
+    ctrl
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsFd;
+
+    use crate::buffer::ReadBuffer;
+
+    use super::*;
+
+    const MESSAGE: Message = Message {
+        object_id: 42,
+        opcode: 69,
+        body: &[1, 2, 3, 4, 5, 6, 7, 8],
+    };
+
+    #[test]
+    fn send_single_message() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        let sent = send_message(&sender, &MESSAGE, &[fd.as_fd()]).unwrap();
+        assert_eq!(sent, MESSAGE.wire_len());
+
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.parse_message(), Some(MESSAGE));
+        assert!(buffer.parse_fd().is_some());
+        assert!(buffer.parse_fd().is_none());
+    }
+
+    #[test]
+    fn reject_long_message() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        receiver.set_nonblocking(true).unwrap();
+
+        // the length of this message would wrap around to 0 in the 16 bit header field
+        let body = vec![0; crate::message::MAX_MESSAGE_LEN - 7];
+        let message = Message {
+            object_id: 42,
+            opcode: 69,
+            body: &body,
+        };
+
+        let error = send_message(&sender, &message, &[]).unwrap_err();
+        assert_eq!(error.error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!error.is_partial());
+
+        let streams = [sender];
+        let errors = broadcast(&streams, &message, &[]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].1.error.kind(), io::ErrorKind::InvalidInput);
+
+        // nothing should have been written to the stream
+        let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+        assert!(!buffer.read_from_stream(&receiver).unwrap());
+        assert_eq!(buffer.pending_bytes(), 0);
+    }
+
+    #[test]
+    fn broadcast_collects_errors() {
+        let (sender_a, receiver_a) = UnixStream::pair().unwrap();
+        let (sender_b, receiver_b) = UnixStream::pair().unwrap();
+        let (sender_c, receiver_c) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        // close one of the receivers so sending to it fails
+        drop(receiver_b);
+
+        let streams = [sender_a, sender_b, sender_c];
+        let errors = broadcast(&streams, &MESSAGE, &[fd.as_fd()]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 1);
+
+        for receiver in [&receiver_a, &receiver_c] {
+            let mut buffer = ReadBuffer::new([0; 64], [0; 64]);
+            assert!(buffer.read_from_stream(receiver).unwrap());
+            assert_eq!(buffer.parse_message(), Some(MESSAGE));
+            assert!(buffer.parse_fd().is_some());
+        }
+    }
+
+    #[test]
+    fn partial_send() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+
+        // fds can not be sent on their own
+        let error = send_bytes(&sender, &[], &[fd.as_fd()]).unwrap_err();
+        assert_eq!(error.error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!error.is_partial());
+
+        // a send larger than the socket buffer fills it partway through
+        let bytes = vec![7; 4 * 1024 * 1024];
+        let error = send_bytes(&sender, &bytes, &[fd.as_fd()]).unwrap_err();
+        assert_eq!(error.error.kind(), io::ErrorKind::WouldBlock);
+        assert!(error.is_partial());
+
+        // the fd was sent with the first byte, and the rest can be resumed from `sent`
+        let mut buffer = ReadBuffer::new(vec![0; bytes.len()], vec![0; 64]);
+        assert!(buffer.read_from_stream(&receiver).unwrap());
+        assert!(buffer.parse_fd().is_some());
+
+        let mut sent = error.sent;
+        while buffer.pending_bytes() < bytes.len() {
+            match send_bytes(&sender, &bytes[sent..], &[]) {
+                Ok(len) => sent += len,
+                Err(error) => sent += error.sent,
+            }
+            buffer.read_from_stream(&receiver).unwrap();
+        }
+        assert_eq!(sent, bytes.len());
+    }
+
+    #[test]
+    fn oversized_message() {
+        use std::os::{fd::OwnedFd, unix::net::UnixDatagram};
+
+        // datagrams must fit in the send buffer in one piece, unlike stream data
+        let (sender, _receiver) = UnixDatagram::pair().unwrap();
+        let sender = UnixStream::from(OwnedFd::from(sender));
+        let error = sendmsg(&sender, &vec![0; 16 * 1024 * 1024], &[]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,905 @@
+use std::{
+    collections::VecDeque,
+    io,
+    net::Shutdown,
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd},
+        unix::net::UnixStream,
+    },
+    sync::Arc,
+};
+
+use crate::{Message, OwnedMessage, buffer::ReadBuffer, send};
+
+/// A wayland connection that reads incoming messages into a [`ReadBuffer`]
+pub struct WaylandStream<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    read: ReadHalf<Data, Ctrl>,
+    write: WriteHalf,
+}
+
+impl<Data, Ctrl> WaylandStream<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Returns a new wayland stream that reads from `stream` into `buffer`
+    pub fn new(stream: UnixStream, buffer: ReadBuffer<Data, Ctrl>) -> Self {
+        let stream = Arc::new(stream);
+        Self {
+            read: ReadHalf {
+                stream: stream.clone(),
+                buffer,
+                watermark: None,
+                queued: VecDeque::new(),
+            },
+            write: WriteHalf { stream },
+        }
+    }
+
+    /// Returns a reference to the underlying socket
+    ///
+    /// This can be used to register the socket with an external event loop,
+    /// or to query socket options. Reading from it directly bypasses the read buffer.
+    pub fn get_ref(&self) -> &UnixStream {
+        self.write.get_ref()
+    }
+
+    /// Returns a reference to the internal [`ReadBuffer`]
+    pub fn buffer(&self) -> &ReadBuffer<Data, Ctrl> {
+        &self.read.buffer
+    }
+
+    /// Returns a mutable reference to the internal [`ReadBuffer`]
+    pub fn buffer_mut(&mut self) -> &mut ReadBuffer<Data, Ctrl> {
+        &mut self.read.buffer
+    }
+
+    /// Reads as many bytes from the stream as possible
+    ///
+    /// Returns `true` if any data was received from the socket
+    pub fn read(&mut self) -> io::Result<bool> {
+        self.read.read()
+    }
+
+    /// Copies the next bytes waiting on the socket into `buf` without consuming them
+    ///
+    /// See [`ReadHalf::peek`] for more details.
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read.peek(buf)
+    }
+
+    /// Blocks until a message matching `predicate` arrives and returns it
+    ///
+    /// See [`ReadHalf::read_until`] for more details.
+    pub fn read_until(
+        &mut self,
+        predicate: impl FnMut(&Message) -> bool,
+    ) -> io::Result<OwnedMessage> {
+        self.read.read_until(predicate)
+    }
+
+    /// Removes the oldest message skipped by [`read_until`](Self::read_until)
+    pub fn pop_queued(&mut self) -> Option<OwnedMessage> {
+        self.read.pop_queued()
+    }
+
+    /// Enables or disables kernel receive timestamps on the socket
+    ///
+    /// See [`ReadHalf::set_timestamps`] for more details.
+    pub fn set_timestamps(&self, enabled: bool) -> io::Result<()> {
+        self.read.set_timestamps(enabled)
+    }
+
+    /// Returns the size of the kernel receive buffer for the socket in bytes
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        self.read.recv_buffer_size()
+    }
+
+    /// Sets the size of the kernel receive buffer for the socket
+    ///
+    /// See [`ReadHalf::set_recv_buffer_size`] for more details.
+    pub fn set_recv_buffer_size(&self, bytes: usize) -> io::Result<()> {
+        self.read.set_recv_buffer_size(bytes)
+    }
+
+    /// Returns the size of the kernel send buffer for the socket in bytes
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        self.write.send_buffer_size()
+    }
+
+    /// Sets the size of the kernel send buffer for the socket
+    ///
+    /// See [`WriteHalf::set_send_buffer_size`] for more details.
+    pub fn set_send_buffer_size(&self, bytes: usize) -> io::Result<()> {
+        self.write.set_send_buffer_size(bytes)
+    }
+
+    /// Sets a callback that is called when the pending bytes in the read buffer reach `level`
+    ///
+    /// See [`ReadHalf::set_high_watermark`] for more details.
+    pub fn set_high_watermark(
+        &mut self,
+        level: usize,
+        callback: impl FnMut(usize) + Send + 'static,
+    ) {
+        self.read.set_high_watermark(level, callback)
+    }
+
+    /// Removes the callback set by [`set_high_watermark`](Self::set_high_watermark)
+    pub fn clear_high_watermark(&mut self) {
+        self.read.clear_high_watermark()
+    }
+
+    /// Parse the next [`Message`] from the read buffer
+    ///
+    /// Returns `None` if there are none left in the buffer
+    pub fn parse_message(&mut self) -> Option<Message<'_>> {
+        self.read.parse_message()
+    }
+
+    /// Parses every [`Message`] in the read buffer, calling `f` for each one
+    ///
+    /// Returns the number of messages that were parsed
+    pub fn for_each_message(&mut self, f: impl FnMut(Message)) -> usize {
+        self.read.for_each_message(f)
+    }
+
+    /// Parses at most `limit` messages from the read buffer, calling `f` for each one
+    ///
+    /// Returns `true` if there are more full messages waiting to be parsed
+    pub fn for_each_message_limited(&mut self, limit: usize, f: impl FnMut(Message)) -> bool {
+        self.read.for_each_message_limited(limit, f)
+    }
+
+    /// Parse the next [`OwnedFd`] from the read buffer
+    ///
+    /// Returns `None` if there are none left in the buffer
+    pub fn parse_fd(&mut self) -> Option<OwnedFd> {
+        self.read.parse_fd()
+    }
+
+    /// Parse the next [`RawFd`] from the read buffer
+    ///
+    /// The caller becomes responsible for closing the returned fd.
+    ///
+    /// Returns `None` if there are none left in the buffer
+    pub fn parse_raw_fd(&mut self) -> Option<RawFd> {
+        self.read.parse_raw_fd()
+    }
+
+    /// Sends a single [`Message`] with `fds` attached
+    ///
+    /// Returns the number of bytes written to the stream.
+    /// See [`SendError`](send::SendError) for how to handle a partial send.
+    pub fn send(&self, message: &Message, fds: &[BorrowedFd]) -> Result<usize, send::SendError> {
+        self.write.send(message, fds)
+    }
+
+    /// Shuts down the write side of the stream, while still allowing reads
+    ///
+    /// This can be used to tear down a misbehaving client gracefully, by sending a final
+    /// error event and then closing the write side while draining any remaining requests.
+    pub fn shutdown_write(&self) -> io::Result<()> {
+        self.write.shutdown_write()
+    }
+
+    /// Shuts down both the read and write sides of the stream
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.write.shutdown()
+    }
+
+    /// Splits this stream into independent read and write halves
+    ///
+    /// The halves share the same underlying socket,
+    /// which is only closed once both halves have been dropped.
+    pub fn split(self) -> (ReadHalf<Data, Ctrl>, WriteHalf) {
+        (self.read, self.write)
+    }
+}
+
+impl<Data, Ctrl> AsFd for WaylandStream<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Borrows the underlying socket, which can be registered with an external event loop
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.write.as_fd()
+    }
+}
+
+impl<Data, Ctrl> AsRawFd for WaylandStream<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn as_raw_fd(&self) -> RawFd {
+        self.write.as_raw_fd()
+    }
+}
+
+/// The read half of a [`WaylandStream`] that owns the read buffer
+pub struct ReadHalf<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    stream: Arc<UnixStream>,
+    buffer: ReadBuffer<Data, Ctrl>,
+    watermark: Option<Watermark>,
+    queued: VecDeque<OwnedMessage>,
+}
+
+/// Blocks until `fd` is readable, retrying if interrupted
+fn wait_readable(fd: BorrowedFd) -> io::Result<()> {
+    let mut pollfd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    loop {
+        if unsafe { libc::poll(&mut pollfd, 1, -1) } >= 0 {
+            return Ok(());
+        }
+
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Sets the `SOL_SOCKET` level option `name` on `fd` to `value`
+fn set_socket_option(fd: BorrowedFd, name: libc::c_int, value: libc::c_int) -> io::Result<()> {
+    let result = unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Gets the value of the `SOL_SOCKET` level option `name` on `fd`
+fn get_socket_option(fd: BorrowedFd, name: libc::c_int) -> io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            name,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(value)
+}
+
+/// Converts a buffer size to a socket option value, saturating sizes that are too large
+fn clamp_size(bytes: usize) -> libc::c_int {
+    bytes.min(libc::c_int::MAX as usize) as libc::c_int
+}
+
+/// A callback that is triggered when the read buffer fills past a level
+struct Watermark {
+    level: usize,
+    above: bool,
+    callback: Box<dyn FnMut(usize) + Send>,
+}
+
+impl<Data, Ctrl> ReadHalf<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Returns a reference to the underlying socket
+    pub fn get_ref(&self) -> &UnixStream {
+        &self.stream
+    }
+
+    /// Returns a reference to the internal [`ReadBuffer`]
+    pub fn buffer(&self) -> &ReadBuffer<Data, Ctrl> {
+        &self.buffer
+    }
+
+    /// Returns a mutable reference to the internal [`ReadBuffer`]
+    pub fn buffer_mut(&mut self) -> &mut ReadBuffer<Data, Ctrl> {
+        &mut self.buffer
+    }
+
+    /// Reads as many bytes from the stream as possible
+    ///
+    /// Returns `true` if any data was received from the socket
+    pub fn read(&mut self) -> io::Result<bool> {
+        // queued messages have not had their fds taken yet, so pending fds may still belong to them
+        let strict_fds = self.buffer.strict_fds();
+        self.buffer
+            .set_strict_fds(strict_fds && self.queued.is_empty());
+        let received = self.buffer.read_from_stream(&self.stream);
+        self.buffer.set_strict_fds(strict_fds);
+        let received = received?;
+
+        // only trigger the watermark when crossing it, not on every read while above it
+        if let Some(watermark) = &mut self.watermark {
+            let pending = self.buffer.pending_bytes();
+            let above = pending >= watermark.level;
+            if above && !watermark.above {
+                (watermark.callback)(pending);
+            }
+            watermark.above = above;
+        }
+
+        Ok(received)
+    }
+
+    /// Copies the next bytes waiting on the socket into `buf` without consuming them
+    ///
+    /// The peeked bytes are still received by the next [`read`](Self::read), and bytes already
+    /// in the read buffer are not included. This can be used to inspect the start of the next
+    /// message, for example to route it, before committing to reading it.
+    ///
+    /// File descriptors cannot be peeked, as the kernel would duplicate them into this process.
+    /// Any fds sent alongside the peeked bytes stay queued on the socket until the next read.
+    ///
+    /// Returns the number of bytes copied into `buf`, or a `WouldBlock` error if nothing is waiting
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let buf_ptr = buf.as_mut_ptr() as *mut libc::c_void;
+        let flags = libc::MSG_PEEK | libc::MSG_DONTWAIT;
+        let len = unsafe { libc::recv(self.stream.as_raw_fd(), buf_ptr, buf.len(), flags) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(len as usize)
+    }
+
+    /// Blocks until a message matching `predicate` arrives and returns it
+    ///
+    /// Messages that do not match are queued, and must be handled with [`pop_queued`](Self::pop_queued)
+    /// before any message parsed from the read buffer, so that they are processed in order.
+    /// Their fds also stay in the read buffer ahead of the fds of any later message.
+    /// While any messages are queued, [`read`](Self::read) does not treat pending fds as unclaimed
+    /// when [`ReadBuffer::strict_fds`] is enabled, as the queued messages may still claim them.
+    /// Queued messages are checked first, so a message skipped by an earlier call can still be returned.
+    ///
+    /// This is used for roundtrips, such as sending `wl_display::sync` and waiting for `wl_callback::done`.
+    ///
+    /// Returns an `UnexpectedEof` error if the stream closes before a matching message arrives
+    pub fn read_until(
+        &mut self,
+        mut predicate: impl FnMut(&Message) -> bool,
+    ) -> io::Result<OwnedMessage> {
+        let queued = self.queued.iter().position(|m| predicate(&m.as_message()));
+        if let Some(message) = queued.and_then(|index| self.queued.remove(index)) {
+            return Ok(message);
+        }
+
+        loop {
+            while let Some(message) = self.buffer.parse_message() {
+                if predicate(&message) {
+                    return Ok(message.into());
+                }
+
+                self.queued.push_back(message.into());
+            }
+
+            wait_readable(self.stream.as_fd())?;
+            if !self.read()? && self.buffer.is_closed() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "stream closed before a matching message arrived",
+                ));
+            }
+        }
+    }
+
+    /// Removes the oldest message skipped by [`read_until`](Self::read_until)
+    ///
+    /// Returns `None` if there are no queued messages
+    pub fn pop_queued(&mut self) -> Option<OwnedMessage> {
+        self.queued.pop_front()
+    }
+
+    /// Enables or disables kernel receive timestamps on the socket
+    ///
+    /// When enabled, the kernel records when data arrives at the socket using `SO_TIMESTAMPNS`.
+    /// The timestamp for the most recent read is then available from [`ReadBuffer::timestamp`],
+    /// which can be used to measure the latency between a client sending input and it being handled.
+    ///
+    /// Linux only delivers timestamps for `SOCK_SEQPACKET` unix sockets.
+    /// This succeeds on a `SOCK_STREAM` socket, but no timestamps will be received.
+    pub fn set_timestamps(&self, enabled: bool) -> io::Result<()> {
+        set_socket_option(
+            self.stream.as_fd(),
+            libc::SO_TIMESTAMPNS,
+            enabled as libc::c_int,
+        )
+    }
+
+    /// Returns the size of the kernel receive buffer for the socket in bytes
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        get_socket_option(self.stream.as_fd(), libc::SO_RCVBUF).map(|size| size as usize)
+    }
+
+    /// Sets the size of the kernel receive buffer for the socket using `SO_RCVBUF`
+    ///
+    /// The kernel doubles the requested size to leave room for its own bookkeeping,
+    /// and clamps it between a minimum and `net.core.rmem_max`,
+    /// so [`recv_buffer_size`](Self::recv_buffer_size) will not return the same value.
+    pub fn set_recv_buffer_size(&self, bytes: usize) -> io::Result<()> {
+        set_socket_option(self.stream.as_fd(), libc::SO_RCVBUF, clamp_size(bytes))
+    }
+
+    /// Sets a callback that is called when the pending bytes in the read buffer reach `level`
+    ///
+    /// The callback receives the number of pending bytes, and can be used to apply backpressure
+    /// to a client that sends messages faster than they are processed, for example by
+    /// removing the stream from an event loop until its messages have been drained.
+    ///
+    /// The level is checked after each [`read`](Self::read), and the callback is only called
+    /// when the pending bytes cross the level. It will not be called again until a read
+    /// finds the pending bytes have dropped back below the level.
+    pub fn set_high_watermark(
+        &mut self,
+        level: usize,
+        callback: impl FnMut(usize) + Send + 'static,
+    ) {
+        self.watermark = Some(Watermark {
+            level,
+            above: false,
+            callback: Box::new(callback),
+        });
+    }
+
+    /// Removes the callback set by [`set_high_watermark`](Self::set_high_watermark)
+    pub fn clear_high_watermark(&mut self) {
+        self.watermark = None;
+    }
+
+    /// Parse the next [`Message`] from the read buffer
+    ///
+    /// Returns `None` if there are none left in the buffer
+    pub fn parse_message(&mut self) -> Option<Message<'_>> {
+        self.buffer.parse_message()
+    }
+
+    /// Parses every [`Message`] in the read buffer, calling `f` for each one
+    ///
+    /// Returns the number of messages that were parsed
+    pub fn for_each_message(&mut self, f: impl FnMut(Message)) -> usize {
+        self.buffer.for_each_message(f)
+    }
+
+    /// Parses at most `limit` messages from the read buffer, calling `f` for each one
+    ///
+    /// Returns `true` if there are more full messages waiting to be parsed
+    pub fn for_each_message_limited(&mut self, limit: usize, f: impl FnMut(Message)) -> bool {
+        self.buffer.for_each_message_limited(limit, f)
+    }
+
+    /// Parse the next [`OwnedFd`] from the read buffer
+    ///
+    /// Returns `None` if there are none left in the buffer
+    pub fn parse_fd(&mut self) -> Option<OwnedFd> {
+        self.buffer.parse_fd()
+    }
+
+    /// Parse the next [`RawFd`] from the read buffer
+    ///
+    /// The caller becomes responsible for closing the returned fd.
+    ///
+    /// Returns `None` if there are none left in the buffer
+    pub fn parse_raw_fd(&mut self) -> Option<RawFd> {
+        self.buffer.parse_raw_fd()
+    }
+}
+
+impl<Data, Ctrl> AsFd for ReadHalf<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+impl<Data, Ctrl> AsRawFd for ReadHalf<Data, Ctrl>
+where
+    Data: AsRef<[u8]> + AsMut<[u8]>,
+    Ctrl: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+/// The write half of a [`WaylandStream`]
+#[derive(Clone)]
+pub struct WriteHalf {
+    stream: Arc<UnixStream>,
+}
+
+impl WriteHalf {
+    /// Returns a reference to the underlying socket
+    pub fn get_ref(&self) -> &UnixStream {
+        &self.stream
+    }
+
+    /// Sends a single [`Message`] with `fds` attached
+    ///
+    /// Returns the number of bytes written to the stream.
+    /// See [`SendError`](send::SendError) for how to handle a partial send.
+    pub fn send(&self, message: &Message, fds: &[BorrowedFd]) -> Result<usize, send::SendError> {
+        send::send_message(&self.stream, message, fds)
+    }
+
+    /// Returns the size of the kernel send buffer for the socket in bytes
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        get_socket_option(self.stream.as_fd(), libc::SO_SNDBUF).map(|size| size as usize)
+    }
+
+    /// Sets the size of the kernel send buffer for the socket using `SO_SNDBUF`
+    ///
+    /// Like [`ReadHalf::set_recv_buffer_size`], the kernel doubles and clamps the requested size.
+    /// Shrinking the send buffer causes writes to a slow peer to be partial or to block sooner.
+    pub fn set_send_buffer_size(&self, bytes: usize) -> io::Result<()> {
+        set_socket_option(self.stream.as_fd(), libc::SO_SNDBUF, clamp_size(bytes))
+    }
+
+    /// Shuts down the write side of the stream
+    ///
+    /// This affects the shared socket, so the peer will see the end of the stream
+    /// even if other clones of this half still exist.
+    pub fn shutdown_write(&self) -> io::Result<()> {
+        self.stream.shutdown(Shutdown::Write)
+    }
+
+    /// Shuts down both the read and write sides of the stream
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.stream.shutdown(Shutdown::Both)
+    }
+}
+
+impl AsFd for WriteHalf {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+impl AsRawFd for WriteHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    const MESSAGE: Message = Message {
+        object_id: 42,
+        opcode: 69,
+        body: &[1, 2, 3, 4],
+    };
+
+    #[test]
+    fn split_read_write() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+
+        let (_, a_write) = a.split();
+        let (mut b_read, _b_write) = b.split();
+
+        let sender = thread::spawn(move || a_write.send(&MESSAGE, &[]).unwrap());
+        let sent = sender.join().unwrap();
+        assert_eq!(sent, MESSAGE.wire_len());
+
+        assert!(b_read.read().unwrap());
+        assert_eq!(b_read.parse_message(), Some(MESSAGE));
+    }
+
+    #[test]
+    fn peek() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let mut b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+
+        // nothing has been sent yet
+        let mut header = [0; 8];
+        let error = b.peek(&mut header).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+
+        a.send(&MESSAGE, &[fd.as_fd()]).unwrap();
+
+        // peeking the header should leave the whole message on the socket
+        assert_eq!(b.peek(&mut header).unwrap(), 8);
+        assert_eq!(header[..4], MESSAGE.object_id.to_ne_bytes());
+        assert_eq!(b.buffer().pending_bytes(), 0);
+
+        assert!(b.read().unwrap());
+        assert_eq!(b.parse_message(), Some(MESSAGE));
+        assert!(b.parse_fd().is_some());
+    }
+
+    #[test]
+    fn read_until() {
+        const DONE: Message = Message {
+            object_id: 3,
+            opcode: 0,
+            body: &[7, 0, 0, 0],
+        };
+
+        let (a, b) = UnixStream::pair().unwrap();
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let mut b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+
+        let sender = thread::spawn(move || {
+            a.send(&MESSAGE, &[]).unwrap();
+            thread::sleep(std::time::Duration::from_millis(10));
+            a.send(&DONE, &[]).unwrap();
+            a.send(&MESSAGE, &[]).unwrap();
+            a
+        });
+
+        // the earlier message should be queued, and the later one left in the buffer
+        let done = b.read_until(|m| m.object_id == DONE.object_id).unwrap();
+        assert_eq!(done.as_message(), DONE);
+        assert_eq!(b.pop_queued(), Some(MESSAGE.into()));
+        assert_eq!(b.pop_queued(), None);
+
+        // queued messages can still be matched by a later call
+        let a = sender.join().unwrap();
+        let message = b.read_until(|m| m.object_id == MESSAGE.object_id).unwrap();
+        assert_eq!(message.as_message(), MESSAGE);
+
+        // the stream closing should not block forever
+        drop(a);
+        let result = b.read_until(|_| false);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_until_strict_fds() {
+        const DONE: Message = Message {
+            object_id: 3,
+            opcode: 0,
+            body: &[7, 0, 0, 0],
+        };
+
+        let (a, b) = UnixStream::pair().unwrap();
+        let (fd, _) = UnixStream::pair().unwrap();
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let mut b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+        b.buffer_mut().set_strict_fds(true);
+
+        a.send(&MESSAGE, &[fd.as_fd()]).unwrap();
+        a.send(&DONE, &[]).unwrap();
+
+        // the fd of the queued message should not count as unclaimed until it is handled
+        let done = b.read_until(|m| m.object_id == DONE.object_id).unwrap();
+        assert_eq!(done.as_message(), DONE);
+        assert_eq!(b.buffer().unclaimed_fds(), 1);
+        assert!(!b.read().unwrap());
+
+        // once handled without taking its fd, the fd belongs to nothing
+        assert_eq!(b.pop_queued(), Some(MESSAGE.into()));
+        let error = b.read().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(b.buffer().strict_fds());
+
+        // taking the fd of a queued message leaves nothing unclaimed
+        a.send(&MESSAGE, &[fd.as_fd()]).unwrap();
+        a.send(&DONE, &[]).unwrap();
+        b.read_until(|m| m.object_id == DONE.object_id).unwrap();
+        assert_eq!(b.pop_queued(), Some(MESSAGE.into()));
+        assert!(b.buffer_mut().parse_fd().is_some());
+        assert!(!b.read().unwrap());
+    }
+
+    #[test]
+    fn socket_buffer_sizes() {
+        let (a, _b) = UnixStream::pair().unwrap();
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+
+        // the kernel doubles the requested size
+        a.set_send_buffer_size(8192).unwrap();
+        assert_eq!(a.send_buffer_size().unwrap(), 16384);
+        a.set_recv_buffer_size(8192).unwrap();
+        assert_eq!(a.recv_buffer_size().unwrap(), 16384);
+
+        // a small send buffer should force partial writes
+        a.set_send_buffer_size(0).unwrap();
+        a.get_ref().set_nonblocking(true).unwrap();
+        let mut buffer = crate::buffer::WriteBuffer::new();
+        for _ in 0..1024 {
+            buffer.push_message(&MESSAGE, []).unwrap();
+        }
+
+        assert!(!buffer.write_to_stream(a.get_ref()).unwrap());
+        assert!(buffer.pending_bytes() > 0);
+    }
+
+    #[test]
+    fn for_each_message() {
+        const COUNT: usize = 3;
+
+        let (a, b) = UnixStream::pair().unwrap();
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let mut b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+
+        for _ in 0..COUNT {
+            a.send(&MESSAGE, &[]).unwrap();
+        }
+
+        assert!(b.read().unwrap());
+        let parsed = b.for_each_message(|message| assert_eq!(message, MESSAGE));
+        assert_eq!(parsed, COUNT);
+        assert!(b.parse_message().is_none());
+    }
+
+    #[test]
+    fn for_each_message_limited() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let mut b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+
+        for _ in 0..3 {
+            a.send(&MESSAGE, &[]).unwrap();
+        }
+
+        assert!(b.read().unwrap());
+
+        let mut parsed = 0;
+        assert!(b.for_each_message_limited(2, |_| parsed += 1));
+        assert_eq!(parsed, 2);
+
+        // the last message should still be buffered
+        assert!(!b.for_each_message_limited(2, |_| parsed += 1));
+        assert_eq!(parsed, 3);
+    }
+
+    #[test]
+    fn high_watermark() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let mut b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        b.set_high_watermark(MESSAGE.wire_len() * 2, move |pending| {
+            sender.send(pending).unwrap()
+        });
+
+        // a single message stays below the watermark
+        a.send(&MESSAGE, &[]).unwrap();
+        assert!(b.read().unwrap());
+        assert!(receiver.try_recv().is_err());
+
+        // the second message crosses it
+        a.send(&MESSAGE, &[]).unwrap();
+        assert!(b.read().unwrap());
+        assert_eq!(receiver.try_recv(), Ok(MESSAGE.wire_len() * 2));
+
+        // staying above the watermark should not trigger it again
+        a.send(&MESSAGE, &[]).unwrap();
+        assert!(b.read().unwrap());
+        assert!(receiver.try_recv().is_err());
+
+        // once drained, crossing it again should trigger it again
+        assert_eq!(b.for_each_message(|_| ()), 3);
+        a.send(&MESSAGE, &[]).unwrap();
+        assert!(b.read().unwrap());
+        a.send(&MESSAGE, &[]).unwrap();
+        a.send(&MESSAGE, &[]).unwrap();
+        assert!(b.read().unwrap());
+        assert_eq!(receiver.try_recv(), Ok(MESSAGE.wire_len() * 3));
+    }
+
+    #[test]
+    fn receive_timestamps() {
+        use std::time::{Duration, SystemTime};
+
+        use std::os::fd::FromRawFd;
+
+        // timestamps are only delivered for seqpacket sockets
+        let mut fds = [0; 2];
+        let result =
+            unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_SEQPACKET, 0, fds.as_mut_ptr()) };
+        assert_eq!(result, 0);
+        let [a, b] = fds.map(|fd| unsafe { UnixStream::from_raw_fd(fd) });
+
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let mut b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+
+        a.send(&MESSAGE, &[]).unwrap();
+        assert!(b.read().unwrap());
+        assert!(b.buffer().timestamp().is_none());
+
+        let before = SystemTime::now();
+        b.set_timestamps(true).unwrap();
+        a.send(&MESSAGE, &[]).unwrap();
+        assert!(b.read().unwrap());
+        assert_eq!(b.for_each_message(|_| ()), 2);
+
+        // the timestamp comes from the same realtime clock, so allow a little skew
+        let timestamp = b.buffer().timestamp().unwrap();
+        let skew = Duration::from_secs(1);
+        assert!(timestamp + skew >= before && timestamp <= SystemTime::now() + skew);
+
+        // timestamps should never be parsed as fds
+        assert!(b.parse_fd().is_none());
+    }
+
+    #[test]
+    fn shutdown_write() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let mut a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let mut b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+
+        // a final message should still arrive before the end of the stream
+        a.send(&MESSAGE, &[]).unwrap();
+        a.shutdown_write().unwrap();
+        assert!(a.send(&MESSAGE, &[]).is_err());
+
+        assert!(b.read().unwrap());
+        assert_eq!(b.parse_message(), Some(MESSAGE));
+        assert!(!b.read().unwrap());
+
+        // the read side should still be open
+        b.send(&MESSAGE, &[]).unwrap();
+        assert!(a.read().unwrap());
+        assert_eq!(a.parse_message(), Some(MESSAGE));
+    }
+
+    #[test]
+    fn poll_stream_fd() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let a = WaylandStream::new(a, ReadBuffer::new([0; 64], [0; 64]));
+        let b = WaylandStream::new(b, ReadBuffer::new([0; 64], [0; 64]));
+
+        let mut poll_fd = libc::pollfd {
+            fd: b.as_fd().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        // nothing has been sent, so the stream should not be readable
+        assert_eq!(unsafe { libc::poll(&mut poll_fd, 1, 0) }, 0);
+
+        a.send(&MESSAGE, &[]).unwrap();
+        assert_eq!(unsafe { libc::poll(&mut poll_fd, 1, 0) }, 1);
+        assert_eq!(poll_fd.revents & libc::POLLIN, libc::POLLIN);
+
+        // both halves should share the same fd
+        let raw = b.as_raw_fd();
+        assert_eq!(b.get_ref().as_raw_fd(), raw);
+        let (read, write) = b.split();
+        assert_eq!(read.as_raw_fd(), raw);
+        assert_eq!(write.as_raw_fd(), raw);
+        assert_eq!(read.get_ref().as_raw_fd(), raw);
+        assert_eq!(write.get_ref().as_raw_fd(), raw);
+    }
+}
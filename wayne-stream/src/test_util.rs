@@ -0,0 +1,54 @@
+//! Helpers for testing code that sends and receives wayland messages
+//!
+//! This module is only available with the `test-util` feature.
+
+use std::{io, os::unix::net::UnixStream};
+
+use crate::{
+    WaylandStream,
+    buffer::{DEFAULT_DATA_BYTES, DEFAULT_MAX_FDS, ReadBuffer},
+};
+
+/// A [`WaylandStream`] with a heap allocated read buffer, as returned by [`connected_pair`]
+pub type TestStream = WaylandStream<Box<[u8]>, Box<[u8]>>;
+
+/// Returns a client and server stream that are connected to each other
+///
+/// The streams are connected with `socketpair(AF_UNIX, SOCK_STREAM)`,
+/// so no listener has to be bound in `XDG_RUNTIME_DIR`.
+/// The sockets are left in blocking mode, so sends wait for space in the socket buffer,
+/// but reads use `MSG_DONTWAIT` and return immediately when nothing has been sent yet.
+/// Each stream has a read buffer with space for [`DEFAULT_DATA_BYTES`] and [`DEFAULT_MAX_FDS`].
+pub fn connected_pair() -> io::Result<(TestStream, TestStream)> {
+    let (client, server) = UnixStream::pair()?;
+    let buffer = || ReadBuffer::with_fd_capacity(DEFAULT_DATA_BYTES, DEFAULT_MAX_FDS);
+    Ok((
+        WaylandStream::new(client, buffer()),
+        WaylandStream::new(server, buffer()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsFd;
+
+    use crate::Message;
+
+    use super::*;
+
+    #[test]
+    fn send_and_receive() {
+        const MESSAGE: Message = Message {
+            object_id: 1,
+            opcode: 2,
+            body: &[3, 0, 0, 0],
+        };
+
+        let (client, mut server) = connected_pair().unwrap();
+        client.send(&MESSAGE, &[client.as_fd()]).unwrap();
+
+        assert!(server.read().unwrap());
+        assert_eq!(server.parse_message(), Some(MESSAGE));
+        assert!(server.parse_fd().is_some());
+    }
+}
@@ -1,7 +1,8 @@
-use std::process::Command;
+use std::{collections::VecDeque, process::Command};
 
 use wayne::{
-    protocol::protocols::wayland::wl_display::WlDisplayRequest, server::WaylandSocket,
+    protocol::protocols::{DISPLAY_ID, wayland::wl_display::WlDisplayRequest},
+    server::WaylandSocket,
     stream::buffer::ReadBuffer,
 };
 
@@ -37,32 +38,19 @@ fn main() -> anyhow::Result<()> {
 
             // read all pending messages
             while let Some(message) = buffer.parse_message() {
-                // build the message parser
-                log::debug!("parsing message: {message:?}");
-                let Some(_) = WlDisplayRequest::parser(message.opcode) else {
-                    log::error!("invalid opcode");
+                // only the display exists until the client creates more objects
+                if message.object_id != DISPLAY_ID {
+                    log::debug!("skipping message: {message:?}");
                     continue;
-                };
-            }
-
-            // // build and store all new messages
-            // messages.extend(message.parse(recv.data()));
+                }
 
-            // // parse all pending messages
-            // for message in messages.drain(..) {
-            //     // build the message parser
-            //     log::debug!("parsing message: {message:?}");
-            //     let Some(mut parser) = WlDisplayRequest::parser(message.opcode) else {
-            //         log::error!("invalid opcode");
-            //         continue;
-            //     };
-
-            //     // try to parse the message
-            //     match parser.parse(message.body.iter().map(|b| *b).buffer(), &mut *fds) {
-            //         Some(request) => log::info!("{request:?}"),
-            //         None => log::error!("Failed to parse message"),
-            //     }
-            // }
+                // try to parse the message, wl_display requests never carry fds
+                log::debug!("parsing message: {message:?}");
+                match WlDisplayRequest::parse(message.opcode, message.body, &mut VecDeque::new()) {
+                    Ok(request) => log::info!("{request:?}"),
+                    Err(e) => log::error!("Failed to parse message: {e}"),
+                }
+            }
         }
     }
 }
@@ -0,0 +1,84 @@
+use std::io;
+
+use thiserror::Error;
+use wayne_protocol::{ParseError, object::ObjectError, registry::RegistryError};
+use wayne_server::socket::BindError;
+
+/// An error from any layer of wayne
+///
+/// This allows applications to use `?` across binding, reading, and parsing.
+/// The granular errors from each crate are still available through each variant.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Bind(#[from] BindError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Object(#[from] ObjectError),
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+}
+
+/// The layer an [`Error`] originated from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The wayland socket could not be bound
+    Bind,
+    /// An io operation on a socket or stream failed
+    Io,
+    /// A message could not be parsed
+    Parse,
+    /// A message referenced an invalid object
+    Object,
+    /// A global could not be bound
+    Registry,
+}
+
+impl Error {
+    /// Returns the kind of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Bind(_) => ErrorKind::Bind,
+            Self::Io(_) => ErrorKind::Io,
+            Self::Parse(_) => ErrorKind::Parse,
+            Self::Object(_) => ErrorKind::Object,
+            Self::Registry(_) => ErrorKind::Registry,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wayne_protocol::types::id::GlobalName;
+
+    use super::*;
+
+    const UNKNOWN_OPCODE: ParseError = ParseError::UnknownOpcode {
+        interface: "wl_compositor",
+        opcode: 3,
+    };
+
+    #[test]
+    fn error_kinds() {
+        fn parse() -> Result<(), Error> {
+            Err(UNKNOWN_OPCODE)?
+        }
+
+        fn object() -> Result<(), Error> {
+            Err(ObjectError::Missing(3))?
+        }
+
+        fn registry() -> Result<(), Error> {
+            Err(RegistryError::UnknownGlobal(GlobalName(3)))?
+        }
+
+        let error = parse().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Parse);
+        assert_eq!(error.to_string(), UNKNOWN_OPCODE.to_string());
+        assert_eq!(object().unwrap_err().kind(), ErrorKind::Object);
+        assert_eq!(registry().unwrap_err().kind(), ErrorKind::Registry);
+    }
+}
@@ -1,3 +1,7 @@
+pub mod error;
+
+pub use error::{Error, ErrorKind};
+
 pub mod server {
     pub use wayne_server::*;
 }